@@ -11,6 +11,38 @@ pub enum Action {
 
     ExtractToggle,
     RecursiveModeToggle,
+    OverwritePolicyToggle,
+    RawModeToggle,
+    AudioFormatToggle,
+    TextureFormatToggle,
+    FilterEdit,
+    DryRun,
+
+    InputChar(char),
+    InputBackspace,
+    InputConfirm,
+    InputCancel,
+    InputUp,
+    InputDown,
+
+    LogToggle,
+    LogClear,
+    LogSave,
+    LogScrollUp,
+    LogScrollDown,
+
+    HexToggle,
+    HexCacheToggle,
+
+    /// Scrolls the hex dump, or the text preview when the dump is hidden
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+
+    SearchEdit,
+    SearchNext,
+    SearchPrevious,
 
     Tick,
     Render,
@@ -31,6 +63,26 @@ impl From<&Event> for Action {
                 KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter => Action::NavigateIn,
                 KeyCode::Char(' ') => Action::ExtractToggle,
                 KeyCode::Char('r') | KeyCode::Char('R') => Action::RecursiveModeToggle,
+                KeyCode::Char('o') | KeyCode::Char('O') => Action::OverwritePolicyToggle,
+                KeyCode::Char('p') | KeyCode::Char('P') => Action::RawModeToggle,
+                KeyCode::Char('a') | KeyCode::Char('A') => Action::AudioFormatToggle,
+                KeyCode::Char('t') | KeyCode::Char('T') => Action::TextureFormatToggle,
+                KeyCode::Char('f') | KeyCode::Char('F') | KeyCode::Char('/') => Action::FilterEdit,
+                KeyCode::Char('d') | KeyCode::Char('D') => Action::DryRun,
+                KeyCode::Char('v') | KeyCode::Char('V') => Action::LogToggle,
+                KeyCode::Char('c') | KeyCode::Char('C') => Action::LogClear,
+                KeyCode::Char('s') | KeyCode::Char('S') => Action::LogSave,
+                KeyCode::PageUp => Action::LogScrollUp,
+                KeyCode::PageDown => Action::LogScrollDown,
+                KeyCode::Char('x') | KeyCode::Char('X') => Action::HexToggle,
+                KeyCode::Char('z') | KeyCode::Char('Z') => Action::HexCacheToggle,
+                KeyCode::Char('K') => Action::ScrollUp,
+                KeyCode::Char('J') => Action::ScrollDown,
+                KeyCode::Char('[') => Action::PageUp,
+                KeyCode::Char(']') => Action::PageDown,
+                KeyCode::Char('?') => Action::SearchEdit,
+                KeyCode::Char('n') => Action::SearchNext,
+                KeyCode::Char('N') => Action::SearchPrevious,
                 KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => Action::Quit,
                 _ => Action::None,
             },
@@ -38,3 +90,22 @@ impl From<&Event> for Action {
         }
     }
 }
+
+impl Action {
+    /// Maps an event to an action while a text field or a dialog has the
+    /// focus.
+    pub fn input(event: &Event) -> Self {
+        match event {
+            Event::Key(key) => match key.code {
+                KeyCode::Char(c) => Action::InputChar(c),
+                KeyCode::Backspace => Action::InputBackspace,
+                KeyCode::Enter => Action::InputConfirm,
+                KeyCode::Esc => Action::InputCancel,
+                KeyCode::Up => Action::InputUp,
+                KeyCode::Down => Action::InputDown,
+                _ => Action::None,
+            },
+            event => Action::from(event),
+        }
+    }
+}
@@ -1,18 +1,19 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(test)]
 use std::sync::Arc;
 
-use color_eyre::eyre::ContextCompat;
 use color_eyre::{eyre::Context, Result};
-use lotus_lib::cache_pair::{CachePair, CachePairReader};
 #[cfg(test)]
-use lotus_lib::package::Package;
-use lotus_lib::package::PackageCollection;
-use lotus_lib::package::PackageType;
+use lotus_lib::cache_pair::CachePairReader;
+#[cfg(test)]
+use lotus_lib::package::{Package, PackageType};
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::widgets::Widget;
 use ratatui::Frame;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use wfcache_api::extract::ExtractOptions;
+use wfcache_api::sink::ArchiveFormat;
 
 use crate::action::Action;
 use crate::tui::Tui;
@@ -27,7 +28,10 @@ pub struct App {
 
     explorer_widget: widgets::Explorer,
     info_widget: widgets::Info,
+    hex_widget: widgets::Hex,
+    preview_widget: widgets::Preview,
     extract_widget: widgets::Extract,
+    log_widget: widgets::Log,
 }
 
 impl App {
@@ -35,30 +39,26 @@ impl App {
         cache_windows_directory: PathBuf,
         package_name: String,
         output_directory: PathBuf,
+        extract_options: ExtractOptions,
     ) -> Result<Self> {
-        let mut collection =
-            PackageCollection::<CachePairReader>::new(cache_windows_directory, true)
-                .wrap_err("Failed to initialize package collection")?;
-
-        let package = collection
-            .borrow_mut(&package_name)
-            .wrap_err(format!("Package {} not found", &package_name))?;
-
-        package
-            .borrow_mut(PackageType::H)
-            .map(|cache| cache.read_toc().unwrap());
-        package
-            .borrow_mut(PackageType::F)
-            .map(|cache| cache.read_toc().unwrap());
-        package
-            .borrow_mut(PackageType::B)
-            .map(|cache| cache.read_toc().unwrap());
-
-        let package = Arc::new(collection.take(&package_name).unwrap());
-
-        let explorer_widget = widgets::Explorer::new(package.clone());
-        let info_widget = widgets::Info::new(package.clone());
-        let extract_widget = widgets::Extract::new(package.clone(), &output_directory);
+        let package = wfcache_api::load_package(cache_windows_directory, &package_name)?;
+
+        let explorer_widget = widgets::Explorer::new(package.clone())?;
+        let info_widget = widgets::Info::new(package.clone(), extract_options.handlers.clone());
+        let hex_widget = widgets::Hex::new(package.clone());
+        let preview_widget = widgets::Preview::new(package.clone());
+        // Save the log next to the archive when extracting into one
+        let log_directory = match ArchiveFormat::from_path(&output_directory) {
+            Some(_) => output_directory.parent().unwrap_or(Path::new("")),
+            None => &output_directory,
+        };
+        let log_widget = widgets::Log::new(log_directory);
+        let extract_widget = widgets::Extract::new(
+            package.clone(),
+            &output_directory,
+            extract_options,
+            log_widget.sender(),
+        );
 
         let (action_tx, action_rx) = unbounded_channel();
         Ok(Self {
@@ -69,7 +69,10 @@ impl App {
             package,
             explorer_widget,
             info_widget,
+            hex_widget,
+            preview_widget,
             extract_widget,
+            log_widget,
         })
     }
 
@@ -92,7 +95,12 @@ impl App {
     pub async fn run(&mut self, terminal: &mut Tui) -> Result<()> {
         while !self.exit {
             let event = terminal.next().await?;
-            let action = Action::from(&event);
+            // Keys go to the text field or dialog having the focus, if any
+            let action = if self.extract_widget.has_focus() || self.preview_widget.has_focus() {
+                Action::input(&event)
+            } else {
+                Action::from(&event)
+            };
             if action != Action::None {
                 self.action_tx.send(action)?;
             }
@@ -105,6 +113,11 @@ impl App {
                 }
             }
         }
+
+        self.extract_widget
+            .finish()
+            .await
+            .wrap_err("extract widget finish failed")?;
         Ok(())
     }
 
@@ -122,6 +135,24 @@ impl App {
             .handle(&action)
             .wrap_err("extract widget handle failed")?;
 
+        // handle log widget events
+        self.log_widget.handle(action);
+
+        // handle info widget events
+        self.info_widget.handle(action);
+
+        // handle hex widget events
+        self.hex_widget.handle(action);
+
+        // handle preview widget events, scrolling goes to the hex dump if shown
+        let scroll = matches!(
+            action,
+            Action::ScrollUp | Action::ScrollDown | Action::PageUp | Action::PageDown
+        );
+        if !(scroll && self.hex_widget.is_visible()) {
+            self.preview_widget.handle(action);
+        }
+
         match action {
             Action::Quit => self.exit = true,
             Action::NavigateDown
@@ -131,6 +162,12 @@ impl App {
                 // Update the info widget with the current node only on navigation
                 self.info_widget.set_node(self.explorer_widget.current());
 
+                // Update the hex widget with the current node only on navigation
+                self.hex_widget.set_node(self.explorer_widget.current());
+
+                // Update the preview widget with the current node only on navigation
+                self.preview_widget.set_node(self.explorer_widget.current());
+
                 // Update the extract widget with the current node only on navigation
                 self.extract_widget.set_node(self.explorer_widget.current());
             }
@@ -139,27 +176,88 @@ impl App {
         Ok(())
     }
 
-    fn compute_layout(&self, area: Rect) -> (Rect, Rect, Rect) {
-        let vertical_layout = Layout::vertical([Constraint::Min(10), Constraint::Length(5)]);
-        let [content_area, extract_area] = vertical_layout.areas(area);
+    fn compute_layout(&self, area: Rect) -> (Rect, Rect, Rect, Rect, Rect, Rect) {
+        let log_height = if self.log_widget.is_visible() { 8 } else { 0 };
+        let vertical_layout = Layout::vertical([
+            Constraint::Min(10),
+            Constraint::Length(log_height),
+            Constraint::Length(5),
+        ]);
+        let [content_area, log_area, extract_area] = vertical_layout.areas(area);
 
         let content_layout = Layout::horizontal([Constraint::Length(50), Constraint::Min(0)]);
         let [explorer_area, info_area] = content_layout.areas(content_area);
 
-        (explorer_area, info_area, extract_area)
+        // The text preview and the hex dump share the right-hand side with the
+        // info widget
+        let (preview_height, hex_height) = match (
+            self.preview_widget.is_visible(),
+            self.hex_widget.is_visible(),
+        ) {
+            (true, true) => (40, 40),
+            (true, false) => (65, 0),
+            (false, true) => (0, 60),
+            (false, false) => (0, 0),
+        };
+        let info_layout = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Percentage(preview_height),
+            Constraint::Percentage(hex_height),
+        ]);
+        let [info_area, preview_area, hex_area] = info_layout.areas(info_area);
+
+        (
+            explorer_area,
+            info_area,
+            preview_area,
+            hex_area,
+            log_area,
+            extract_area,
+        )
     }
 }
 
 impl Widget for &App {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let (explorer_area, info_area, extract_area) = self.compute_layout(area);
+        let (explorer_area, info_area, preview_area, hex_area, log_area, extract_area) =
+            self.compute_layout(area);
 
         self.explorer_widget.render(explorer_area, buf);
         self.info_widget.render(info_area, buf);
+        if self.preview_widget.is_visible() {
+            self.preview_widget.render(preview_area, buf);
+        }
+        if self.hex_widget.is_visible() {
+            self.hex_widget.render(hex_area, buf);
+        }
+        if self.log_widget.is_visible() {
+            self.log_widget.render(log_area, buf);
+        }
         self.extract_widget.render(extract_area, buf);
+
+        if let Some(dry_run) = self.extract_widget.dry_run() {
+            dry_run.render(centered(area, 80, 60), buf);
+        }
     }
 }
 
+/// Returns an area centered in the given one, sized in percents of it.
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [_, area, _] = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .areas(area);
+    let [_, area, _] = Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .areas(area);
+    area
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -181,7 +279,13 @@ mod test {
         let package_name = PACKAGE_NAME.to_string();
         let output_directory = PathBuf::from(HOME_DIR).join(OUTPUT_DIRECTORY);
 
-        let app = App::try_init(cache_windows_directory, package_name, output_directory).unwrap();
+        let app = App::try_init(
+            cache_windows_directory,
+            package_name,
+            output_directory,
+            ExtractOptions::default(),
+        )
+        .unwrap();
 
         // Misc package has H, F, and B caches
         assert!(!app.h_cache().files().is_empty());
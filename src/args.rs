@@ -1,4 +1,9 @@
-use clap::Parser;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use wfcache_api::extract::{AudioFormat, ExtractOptions, OverwritePolicy, TextureFormat};
+use wfcache_api::manifest::{self, ManifestFormat};
+use wfcache_api::{Filter, PackageType};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -11,7 +16,335 @@ pub struct Args {
     #[arg(short, long, default_value = "Misc")]
     pub package: String,
 
-    /// Output directory for extracted files
+    /// Output directory for extracted files, or .tar, .tar.zst or .zip archive to write them to
     #[arg(short, long, default_value = "Extracted")]
     pub output: std::path::PathBuf,
+
+    /// Number of files to extract in parallel [default: number of CPUs]
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub jobs: Option<u16>,
+
+    /// Policy applied to output files that already exist
+    #[arg(long, value_enum, default_value_t = Overwrite::Always)]
+    pub overwrite: Overwrite,
+
+    /// Write a manifest of the extracted files in the output directory, merged
+    /// with the JSON manifest already there if any
+    #[arg(long, value_enum)]
+    pub manifest: Option<Manifest>,
+
+    /// Only extract the files that are new or changed since a previous manifest.json
+    /// (CSV manifests are not accepted). Also pass --manifest json to write the
+    /// manifest the next run compares to
+    #[arg(long, value_name = "MANIFEST")]
+    pub since_manifest: Option<std::path::PathBuf>,
+
+    /// Only extract the files whose TOC path matches the glob (e.g. "**/*.wav")
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Do not extract the files whose TOC path matches the glob
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Only extract the files whose TOC path matches the regex
+    #[arg(long, value_name = "REGEX")]
+    pub regex: Vec<String>,
+
+    /// Only extract the files at least this long (e.g. 10KB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only extract the files at most this long (e.g. 2MB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Write the decompressed H payload and the raw F and B payloads of each file
+    /// side by side (e.g. file.png.h, file.png.f, file.png.b) instead of converting them
+    #[arg(long)]
+    pub raw: bool,
+
+    /// Format audio files are exported in
+    #[arg(long, value_enum, default_value_t = Audio::Native)]
+    pub audio_format: Audio,
+
+    /// Format textures are exported in
+    #[arg(long, value_enum, default_value_t = Texture::Png)]
+    pub texture_format: Texture,
+
+    /// Also write the DDS file of each texture next to the PNG or TGA image
+    #[arg(long)]
+    pub keep_dds: bool,
+
+    /// Start an interactive shell instead of the TUI
+    #[arg(short, long)]
+    pub shell: bool,
+
+    /// Commands to run without starting the TUI (e.g. -c "cd /Lotus" -c "ls")
+    #[arg(short = 'c', long = "command", value_name = "COMMAND")]
+    pub commands: Vec<String>,
+
+    /// Command to run without starting the TUI
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Args {
+    /// Returns the number of extraction jobs, defaulting to the number of CPUs.
+    pub fn jobs(&self) -> usize {
+        self.jobs.map(usize::from).unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        })
+    }
+
+    /// Returns the extraction options selected by the arguments, reading the
+    /// previous manifest if any.
+    pub fn extract_options(&self) -> wfcache_api::Result<ExtractOptions> {
+        let since_manifest = self
+            .since_manifest
+            .as_deref()
+            .map(manifest::Manifest::read)
+            .transpose()?;
+
+        let mut filter = Filter::default();
+        for pattern in &self.include {
+            filter.include(pattern)?;
+        }
+        for pattern in &self.exclude {
+            filter.exclude(pattern)?;
+        }
+        for pattern in &self.regex {
+            filter.regex(pattern)?;
+        }
+        if let Some(min_size) = self.min_size {
+            filter.min_size(min_size);
+        }
+        if let Some(max_size) = self.max_size {
+            filter.max_size(max_size);
+        }
+
+        Ok(ExtractOptions {
+            jobs: self.jobs(),
+            overwrite: self.overwrite.into(),
+            manifest: self.manifest.map(Into::into),
+            since_manifest: since_manifest.map(Arc::new),
+            filter,
+            raw: self.raw,
+            audio: self.audio_format.into(),
+            texture: self.texture_format.into(),
+            keep_dds: self.keep_dds,
+            ..Default::default()
+        })
+    }
+}
+
+/// Command parsed from a command string
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+pub struct CommandLine {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List the content of a directory
+    Ls {
+        /// Directory to list
+        #[arg(default_value = ".")]
+        path: std::path::PathBuf,
+
+        /// Use a long listing format showing the H, F and B cache entries
+        #[arg(short, long)]
+        long: bool,
+    },
+
+    /// Change the current directory
+    Cd {
+        /// Directory to change to
+        #[arg(default_value = "/")]
+        path: std::path::PathBuf,
+    },
+
+    /// Print the current directory
+    Pwd,
+
+    /// Display the cache entries of a file or directory
+    Stat {
+        /// Files or directories to display
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
+    },
+
+    /// Extract files and directories into the output directory
+    Extract {
+        /// Files or directories to extract
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
+
+        /// Extract the subdirectories recursively
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Print the files that would be written without extracting anything
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Write the decoded content of a file to stdout
+    Cat {
+        /// File to write
+        path: std::path::PathBuf,
+
+        /// Write the payload of the file as is instead of decoding it
+        #[arg(long)]
+        raw: bool,
+
+        /// Cache whose payload is written, implies --raw
+        #[arg(long, value_enum)]
+        cache: Option<Cache>,
+    },
+
+    /// Search for files and directories
+    Find {
+        /// Directory to search in
+        #[arg(default_value = ".")]
+        path: std::path::PathBuf,
+
+        /// Only show entries whose name matches the glob pattern
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only show entries of the given kind
+        #[arg(short = 't', long = "type", value_enum)]
+        kind: Option<NodeType>,
+    },
+
+    /// Display a directory as a tree
+    Tree {
+        /// Directory to display
+        #[arg(default_value = ".")]
+        path: std::path::PathBuf,
+
+        /// Maximum depth of the tree
+        #[arg(short = 'L', long)]
+        depth: Option<usize>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeType {
+    /// Files
+    #[value(name = "f")]
+    File,
+    /// Directories
+    #[value(name = "d")]
+    Directory,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cache {
+    /// Decompressed payload of the H cache
+    H,
+    /// Payload of the F cache, as stored
+    F,
+    /// Payload of the B cache, as stored
+    B,
+}
+
+impl From<Cache> for PackageType {
+    fn from(cache: Cache) -> Self {
+        match cache {
+            Cache::H => PackageType::H,
+            Cache::F => PackageType::F,
+            Cache::B => PackageType::B,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Always overwrite existing files
+    Always,
+    /// Never overwrite existing files
+    Never,
+    /// Overwrite files older than their cache entry
+    IfNewer,
+    /// Overwrite files whose length differs from the extracted data
+    IfSizeDiffers,
+}
+
+impl From<Overwrite> for OverwritePolicy {
+    fn from(overwrite: Overwrite) -> Self {
+        match overwrite {
+            Overwrite::Always => OverwritePolicy::Always,
+            Overwrite::Never => OverwritePolicy::Never,
+            Overwrite::IfNewer => OverwritePolicy::IfNewer,
+            Overwrite::IfSizeDiffers => OverwritePolicy::IfSizeDiffers,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Manifest {
+    /// Write a manifest.json file
+    Json,
+    /// Write a manifest.csv file
+    Csv,
+}
+
+impl From<Manifest> for ManifestFormat {
+    fn from(manifest: Manifest) -> Self {
+        match manifest {
+            Manifest::Json => ManifestFormat::Json,
+            Manifest::Csv => ManifestFormat::Csv,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Audio {
+    /// WAV for PCM and ADPCM audio, Opus in Ogg for Opus audio
+    Native,
+    /// 16-bit PCM WAV, decoding ADPCM and Opus audio
+    Wav,
+    /// Raw Opus packets, each preceded by its length, encoding PCM and ADPCM audio
+    Opus,
+    /// Opus in Ogg, encoding PCM and ADPCM audio
+    Ogg,
+}
+
+impl From<Audio> for AudioFormat {
+    fn from(audio: Audio) -> Self {
+        match audio {
+            Audio::Native => AudioFormat::Native,
+            Audio::Wav => AudioFormat::Wav,
+            Audio::Opus => AudioFormat::Opus,
+            Audio::Ogg => AudioFormat::Ogg,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Texture {
+    /// PNG image decoded from the DDS file
+    Png,
+    /// TGA image decoded from the DDS file
+    Tga,
+    /// DDS file as stored in the cache
+    Dds,
+}
+
+impl From<Texture> for TextureFormat {
+    fn from(texture: Texture) -> Self {
+        match texture {
+            Texture::Png => TextureFormat::Png,
+            Texture::Tga => TextureFormat::Tga,
+            Texture::Dds => TextureFormat::Dds,
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<u64, String> {
+    wfcache_api::parse_size(size).map_err(|err| err.to_string())
 }
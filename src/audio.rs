@@ -0,0 +1,800 @@
+//! Conversions between the audio formats written by `decompress_audio` and
+//! the ones requested by [`AudioFormat`].
+
+use std::borrow::Cow;
+
+use lotus_lib::package::PackageType;
+use lotus_lib::toc::FileNode;
+use lotus_utils_audio::Audio;
+use mousiki::c_style_api::opus_encoder::{opus_encoder_ctl, OpusEncoderCtlRequest};
+use mousiki::{Application, Bitrate, Channels, Encoder};
+
+use crate::error::ExtractError;
+use crate::extract::{cache_node, AudioFormat, ExtractOptions};
+use crate::format::{FileContext, FormatHandler};
+use crate::header::{read_u32, Header};
+
+/// Compression format tags of the audio headers.
+pub(crate) const PCM: u32 = 0x00;
+pub(crate) const ADPCM: u32 = 0x05;
+pub(crate) const OPUS: u32 = 0x07;
+
+/// File type of the audio headers.
+const AUDIO_KIND: u32 = 0x8B;
+
+/// Coefficients of the Microsoft ADPCM predictors, as written in the WAV
+/// header by `decompress_audio`.
+const ADPCM_COEFFICIENTS: [(i32, i32); 7] = [
+    (256, 0),
+    (512, -256),
+    (0, 0),
+    (192, 64),
+    (240, 0),
+    (460, -208),
+    (392, -232),
+];
+
+const ADPCM_ADAPTATION: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+/// Bitrate of each channel of the Opus streams encoded from PCM and ADPCM
+/// audio.
+const OPUS_BITRATE: i32 = 64_000;
+/// Maximum length of an encoded Opus packet.
+const MAX_PACKET_LEN: usize = 4000;
+/// Serial number of the Ogg streams written when encoding Opus.
+const OGG_SERIAL: u32 = 0x5746_4341;
+
+/// Interleaved 16-bit samples of an audio file.
+struct Pcm {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+/// Handler of the audio files, exported in the format of
+/// [`ExtractOptions::audio`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AudioHandler;
+
+impl FormatHandler for AudioHandler {
+    fn name(&self) -> &str {
+        "Audio"
+    }
+
+    fn detect(&self, file: &FileContext) -> Result<bool, ExtractError> {
+        if !file.node().name().ends_with(".wav") {
+            return Ok(false);
+        }
+
+        // The audio detection of lotus-utils panics on truncated headers
+        let header_data = file.header_data()?;
+        Ok(Header::parse(header_data).is_some_and(|header| {
+            header.file_type == AUDIO_KIND && read_u32(header_data, header.offset + 62).is_some()
+        }))
+    }
+
+    fn output_name(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<String, ExtractError> {
+        let format_tag = check_audio(file)?;
+        Ok(file_name(&file.node().name(), format_tag, options.audio))
+    }
+
+    fn decode(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>, ExtractError> {
+        let format_tag = check_audio(file)?;
+        // The audio decompression parses its own copy of the H payload
+        let data = file
+            .package()
+            .decompress_audio(file.node())
+            .map_err(ExtractError::Decompression)?
+            .0;
+        convert(data, format_tag, options.audio)
+    }
+}
+
+/// Returns the name of an audio file exported in the given format.
+pub(crate) fn file_name(name: &str, format_tag: u32, format: AudioFormat) -> String {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let extension = match (format_tag, format) {
+        (OPUS, AudioFormat::Native) | (_, AudioFormat::Ogg) => "opus",
+        (_, AudioFormat::Opus) => "opus.raw",
+        _ => "wav",
+    };
+    format!("{}.{}", stem, extension)
+}
+
+/// Converts the data returned by `decompress_audio` (a WAV file for PCM and
+/// ADPCM sources, an Ogg Opus stream for Opus sources) to the given format.
+///
+/// # Errors
+///
+/// Returns an error if the data is malformed or cannot be encoded.
+pub(crate) fn convert(
+    data: Vec<u8>,
+    format_tag: u32,
+    format: AudioFormat,
+) -> Result<Vec<u8>, ExtractError> {
+    match (format_tag, format) {
+        (ADPCM, AudioFormat::Wav) => {
+            let pcm = adpcm_samples(&data)?;
+            Ok(pcm_wav(pcm.channels, pcm.sample_rate, &pcm.samples))
+        }
+        (OPUS, AudioFormat::Wav) => opus_to_pcm(&data),
+        (OPUS, AudioFormat::Opus) => {
+            let (_, packets) = opus_packets(&data)?;
+            Ok(raw_opus(packets.iter().map(Vec::as_slice)))
+        }
+        (PCM | ADPCM, AudioFormat::Opus | AudioFormat::Ogg) => {
+            let pcm = match format_tag {
+                PCM => wav_samples(&data)?,
+                _ => adpcm_samples(&data)?,
+            };
+            let packets = encode_opus(&pcm)?;
+            Ok(match format {
+                AudioFormat::Ogg => ogg_stream(&packets),
+                // Skip the OpusHead and OpusTags packets
+                _ => raw_opus(packets[2..].iter().map(|(packet, _)| packet.as_slice())),
+            })
+        }
+        _ => Ok(data),
+    }
+}
+
+/// Checks that an audio file can be decompressed, as the audio decompression
+/// panics on missing cache pairs, truncated data and invalid stream
+/// parameters.
+///
+/// Returns the compression format tag of the audio file.
+fn check_audio(file: &FileContext) -> Result<u32, ExtractError> {
+    let (package, node) = (file.package(), file.node());
+    let header_data = file.header_data()?;
+    let header = Header::parse(header_data)
+        .ok_or_else(|| ExtractError::UnknownFormat(String::from("truncated audio header")))?;
+
+    let format_tag = read_u32(header_data, header.offset);
+    let size = read_u32(header_data, header.offset + 62);
+    let bits_per_sample = header_data.get(header.offset + 36).copied().unwrap_or(0);
+    let channels = header_data.get(header.offset + 37).copied().unwrap_or(0);
+    let block_align = header_data
+        .get(header.offset + 46..header.offset + 48)
+        .map_or(0, |bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+
+    let (format_tag, size) = match (format_tag, size) {
+        (Some(format_tag @ (PCM | ADPCM | OPUS)), Some(size)) => (format_tag, size),
+        (Some(format_tag), Some(_)) => {
+            return Err(ExtractError::UnknownFormat(format!(
+                "audio compression format 0x{:02X}",
+                format_tag
+            )))
+        }
+        _ => {
+            return Err(ExtractError::UnknownFormat(String::from(
+                "truncated audio header",
+            )))
+        }
+    };
+
+    // The WAV header of PCM files computes its block align on a byte, and the
+    // Ogg pages of Opus files hold 50 blocks
+    let invalid = match format_tag {
+        PCM => channels.checked_mul(bits_per_sample).is_none(),
+        OPUS => block_align == 0,
+        _ => false,
+    };
+    if invalid {
+        return Err(ExtractError::UnknownFormat(String::from(
+            "invalid audio stream parameters",
+        )));
+    }
+
+    // Both cache pairs are required even if the data lies in only one of them
+    for package_type in [PackageType::F, PackageType::B] {
+        if package.borrow(package_type).is_none() {
+            return Err(ExtractError::MissingCache(package_type));
+        }
+    }
+
+    let f_node = cache_node(package, PackageType::F, node).ok();
+    let b_node = cache_node(package, PackageType::B, node).ok();
+
+    let data_len: i64 = [&f_node, &b_node]
+        .into_iter()
+        .flatten()
+        .map(|node| node.len() as i64)
+        .sum();
+    if data_len < size as i64 {
+        let package_type = if f_node.is_none() {
+            PackageType::F
+        } else {
+            PackageType::B
+        };
+        return Err(ExtractError::MissingCache(package_type));
+    }
+
+    Ok(format_tag)
+}
+
+/// Reads the samples of a 16-bit PCM WAV file.
+fn wav_samples(wav: &[u8]) -> Result<Pcm, ExtractError> {
+    let malformed = || ExtractError::UnknownFormat(String::from("malformed PCM WAV file"));
+
+    let fmt = riff_chunk(wav, b"fmt ").ok_or_else(malformed)?;
+    let data = riff_chunk(wav, b"data").ok_or_else(malformed)?;
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        Some(u16::from_le_bytes(
+            fmt.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let channels = read_u16(2).ok_or_else(malformed)?;
+    let sample_rate = read_u32(fmt, 4).ok_or_else(malformed)?;
+    let bits_per_sample = read_u16(14).ok_or_else(malformed)?;
+    if bits_per_sample != 16 {
+        return Err(ExtractError::UnknownFormat(format!(
+            "{}-bit PCM audio",
+            bits_per_sample
+        )));
+    }
+
+    let samples = data
+        .chunks_exact(2)
+        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+        .collect();
+    Ok(Pcm {
+        channels,
+        sample_rate,
+        samples,
+    })
+}
+
+/// Decodes the samples of a Microsoft ADPCM WAV file.
+fn adpcm_samples(wav: &[u8]) -> Result<Pcm, ExtractError> {
+    let malformed = || ExtractError::UnknownFormat(String::from("malformed ADPCM WAV file"));
+
+    let fmt = riff_chunk(wav, b"fmt ").ok_or_else(malformed)?;
+    let data = riff_chunk(wav, b"data").ok_or_else(malformed)?;
+
+    let read_u16 = |offset: usize| -> Option<u16> {
+        Some(u16::from_le_bytes(
+            fmt.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let channels = read_u16(2).ok_or_else(malformed)? as usize;
+    let sample_rate = read_u32(fmt, 4).ok_or_else(malformed)?;
+    let block_align = read_u16(12).ok_or_else(malformed)? as usize;
+    let samples_per_block = read_u16(18).ok_or_else(malformed)? as usize;
+
+    if !(1..=2).contains(&channels) || block_align < 7 * channels {
+        return Err(malformed());
+    }
+
+    let mut samples: Vec<i16> = Vec::with_capacity(data.len() * 2);
+    for block in data.chunks(block_align) {
+        decode_adpcm_block(block, channels, samples_per_block, &mut samples);
+    }
+
+    Ok(Pcm {
+        channels: channels as u16,
+        sample_rate,
+        samples,
+    })
+}
+
+/// Writes a 16-bit PCM WAV file of interleaved samples.
+fn pcm_wav(channels: u16, samples_per_second: u32, samples: &[i16]) -> Vec<u8> {
+    let pcm_len = samples.len() as u32 * 2;
+    let mut pcm = Vec::with_capacity(44 + pcm_len as usize);
+    pcm.extend_from_slice(b"RIFF");
+    pcm.extend_from_slice(&(pcm_len + 36).to_le_bytes());
+    pcm.extend_from_slice(b"WAVE");
+    pcm.extend_from_slice(b"fmt ");
+    pcm.extend_from_slice(&16u32.to_le_bytes());
+    pcm.extend_from_slice(&1u16.to_le_bytes());
+    pcm.extend_from_slice(&channels.to_le_bytes());
+    pcm.extend_from_slice(&samples_per_second.to_le_bytes());
+    pcm.extend_from_slice(&(samples_per_second * channels as u32 * 2).to_le_bytes());
+    pcm.extend_from_slice(&(channels * 2).to_le_bytes());
+    pcm.extend_from_slice(&16u16.to_le_bytes());
+    pcm.extend_from_slice(b"data");
+    pcm.extend_from_slice(&pcm_len.to_le_bytes());
+    for sample in samples {
+        pcm.extend_from_slice(&sample.to_le_bytes());
+    }
+    pcm
+}
+
+/// Decodes a block of interleaved Microsoft ADPCM samples. A truncated block
+/// is decoded up to where its data ends.
+fn decode_adpcm_block(block: &[u8], channels: usize, samples_per_block: usize, out: &mut Vec<i16>) {
+    if block.len() < 7 * channels {
+        return;
+    }
+
+    let read_i16 = |offset: usize| i16::from_le_bytes([block[offset], block[offset + 1]]) as i32;
+
+    // The preamble holds, for each channel in turn, the predictor index, the
+    // initial delta and the two first samples (in reverse order)
+    let mut coefficients = [(0, 0); 2];
+    let mut delta = [0; 2];
+    let mut sample1 = [0; 2];
+    let mut sample2 = [0; 2];
+    for channel in 0..channels {
+        let predictor = (block[channel] as usize).min(ADPCM_COEFFICIENTS.len() - 1);
+        coefficients[channel] = ADPCM_COEFFICIENTS[predictor];
+        delta[channel] = read_i16(channels + 2 * channel);
+        sample1[channel] = read_i16(3 * channels + 2 * channel);
+        sample2[channel] = read_i16(5 * channels + 2 * channel);
+    }
+
+    out.extend((0..channels).map(|channel| sample2[channel] as i16));
+    out.extend((0..channels).map(|channel| sample1[channel] as i16));
+
+    // Then come the nibbles, high nibble first, alternating between channels
+    let nibbles = block[7 * channels..]
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0F])
+        .take(samples_per_block.saturating_sub(2) * channels);
+    for (index, nibble) in nibbles.enumerate() {
+        let channel = index % channels;
+        let (coefficient1, coefficient2) = coefficients[channel];
+
+        let signed = if nibble >= 8 {
+            nibble as i32 - 16
+        } else {
+            nibble as i32
+        };
+        let predicted = (sample1[channel] * coefficient1 + sample2[channel] * coefficient2) >> 8;
+        let sample = (predicted + signed * delta[channel]).clamp(i16::MIN as i32, i16::MAX as i32);
+
+        sample2[channel] = sample1[channel];
+        sample1[channel] = sample;
+        delta[channel] = ((ADPCM_ADAPTATION[nibble as usize] * delta[channel]) >> 8).max(16);
+
+        out.push(sample as i16);
+    }
+}
+
+/// Returns the content of the first chunk with the given identifier of a
+/// RIFF file.
+fn riff_chunk<'a>(riff: &'a [u8], id: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut offset = 12;
+    while offset + 8 <= riff.len() {
+        let len = read_u32(riff, offset + 4)? as usize;
+        let content = offset + 8;
+        if &riff[offset..offset + 4] == id {
+            return riff.get(content..content + len);
+        }
+        // Chunks are padded to an even length
+        offset = content + len + (len & 1);
+    }
+    None
+}
+
+/// Decodes an Ogg Opus stream to a 16-bit PCM WAV file, at the input sample
+/// rate of the stream if the decoder supports it and at 48 kHz otherwise.
+fn opus_to_pcm(ogg: &[u8]) -> Result<Vec<u8>, ExtractError> {
+    let malformed = || ExtractError::UnknownFormat(String::from("malformed Opus header"));
+    let decoding = |err: opus_decoder::OpusError| ExtractError::Conversion(err.to_string());
+
+    let (head, packets) = opus_packets(ogg)?;
+    if head.len() < 19 {
+        return Err(malformed());
+    }
+    let channels = head[9];
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]);
+    let input_sample_rate = read_u32(&head, 12).ok_or_else(malformed)?;
+    let channel_mapping_family = head[18];
+    if channel_mapping_family != 0 || !(1..=2).contains(&channels) {
+        return Err(ExtractError::UnknownFormat(format!(
+            "Opus channel mapping {} with {} channels",
+            channel_mapping_family, channels
+        )));
+    }
+
+    let sample_rate = match input_sample_rate {
+        8_000 | 12_000 | 16_000 | 24_000 | 48_000 => input_sample_rate,
+        _ => 48_000,
+    };
+    let channels = channels as usize;
+    let mut decoder = opus_decoder::OpusDecoder::new(sample_rate, channels).map_err(decoding)?;
+
+    let mut frame = vec![0i16; decoder.max_frame_size_per_channel() * channels];
+    let mut samples = Vec::new();
+    for packet in &packets {
+        let len = decoder
+            .decode(packet, &mut frame, false)
+            .map_err(decoding)?;
+        samples.extend_from_slice(&frame[..len * channels]);
+    }
+
+    // The pre-skip is counted at 48 kHz
+    let skipped = pre_skip as usize * sample_rate as usize / 48_000 * channels;
+    let samples = samples.get(skipped..).unwrap_or_default();
+
+    Ok(pcm_wav(channels as u16, sample_rate, samples))
+}
+
+/// Returns the OpusHead packet and the audio packets of an Ogg Opus stream.
+fn opus_packets(ogg: &[u8]) -> Result<(Vec<u8>, Vec<Vec<u8>>), ExtractError> {
+    let mut packets = ogg_packets(ogg)?.into_iter();
+    match (packets.next(), packets.next()) {
+        // The second packet holds the OpusTags comments
+        (Some(head), Some(_)) if head.starts_with(b"OpusHead") => Ok((head, packets.collect())),
+        _ => Err(ExtractError::UnknownFormat(String::from(
+            "malformed Opus header",
+        ))),
+    }
+}
+
+/// Encodes samples to an Opus stream of 20 ms packets, resampled to 48 kHz
+/// if the encoder does not support their sample rate.
+///
+/// Returns the packets of the stream, starting with the OpusHead and
+/// OpusTags packets, with the granule position of each.
+fn encode_opus(pcm: &Pcm) -> Result<Vec<(Vec<u8>, u64)>, ExtractError> {
+    let encoding = |err: &dyn std::fmt::Debug| {
+        ExtractError::Conversion(format!("failed to encode Opus: {:?}", err))
+    };
+
+    let channels = match pcm.channels {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        channels => {
+            return Err(ExtractError::UnknownFormat(format!(
+                "audio with {} channels",
+                channels
+            )))
+        }
+    };
+    let (sample_rate, samples) = match pcm.sample_rate {
+        8_000 | 12_000 | 16_000 | 24_000 | 48_000 => (pcm.sample_rate, Cow::from(&pcm.samples)),
+        0 => return Err(ExtractError::UnknownFormat(String::from("0 Hz audio"))),
+        _ => (
+            48_000,
+            Cow::from(resample(
+                &pcm.samples,
+                pcm.channels as usize,
+                pcm.sample_rate,
+                48_000,
+            )),
+        ),
+    };
+
+    let mut encoder = Encoder::builder(sample_rate, channels, Application::Audio)
+        .bitrate(Bitrate::Bits(OPUS_BITRATE * pcm.channels as i32))
+        .build()
+        .map_err(|err| encoding(&err))?;
+    let mut lookahead = 0;
+    opus_encoder_ctl(
+        encoder.as_raw_mut(),
+        OpusEncoderCtlRequest::GetLookahead(&mut lookahead),
+    )
+    .map_err(|err| encoding(&err))?;
+
+    // Granule positions count samples at 48 kHz
+    let scale = (48_000 / sample_rate) as u64;
+    let pre_skip = lookahead.max(0) as u64 * scale;
+    let channels = pcm.channels as usize;
+    let frame_len = sample_rate as usize / 50;
+    let sample_count = samples.len() / channels;
+    let end = pre_skip + sample_count as u64 * scale;
+
+    // Pad with silence to flush the lookahead and fill the last frame
+    let mut samples = samples.into_owned();
+    let frame_count = (sample_count + lookahead.max(0) as usize).div_ceil(frame_len);
+    samples.resize(frame_count.max(1) * frame_len * channels, 0);
+
+    let mut packets = vec![
+        (
+            opus_head(pcm.channels as u8, pre_skip as u16, pcm.sample_rate),
+            0,
+        ),
+        (opus_tags(), 0),
+    ];
+    for (index, frame) in samples.chunks_exact(frame_len * channels).enumerate() {
+        let packet = encoder
+            .encode_vec(frame, MAX_PACKET_LEN)
+            .map_err(|err| encoding(&err))?;
+        let granule = ((index + 1) * frame_len) as u64 * scale;
+        packets.push((packet, granule.min(end)));
+    }
+
+    Ok(packets)
+}
+
+/// Resamples interleaved samples by linear interpolation.
+fn resample(samples: &[i16], channels: usize, from: u32, to: u32) -> Vec<i16> {
+    let (from, to) = (from as u64, to as u64);
+    let frame_count = (samples.len() / channels) as u64 * to / from;
+
+    let mut resampled = Vec::with_capacity(frame_count as usize * channels);
+    for frame in 0..frame_count {
+        let position = frame * from;
+        let index = (position / to) as usize;
+        let fraction = (position % to) as i64;
+        for channel in 0..channels {
+            let a = samples[index * channels + channel] as i64;
+            let b = samples
+                .get((index + 1) * channels + channel)
+                .map_or(a, |&b| b as i64);
+            resampled.push((a + (b - a) * fraction / to as i64) as i16);
+        }
+    }
+    resampled
+}
+
+/// Writes the OpusHead packet of a stream without channel mapping.
+fn opus_head(channels: u8, pre_skip: u16, input_sample_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_sample_rate.to_le_bytes());
+    // Output gain and channel mapping family
+    head.extend_from_slice(&[0, 0, 0]);
+    head
+}
+
+/// Writes an OpusTags packet without comments.
+fn opus_tags() -> Vec<u8> {
+    let vendor = concat!("wfcache-api ", env!("CARGO_PKG_VERSION"));
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+/// Writes Opus packets in the bitstream format of `opus_demo`: each packet
+/// is preceded by its length and the final range of its encoder as big endian
+/// `u32`s, the range being left to 0 as it is not known.
+fn raw_opus<'a>(packets: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut raw = Vec::new();
+    for packet in packets {
+        raw.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        raw.extend_from_slice(&0u32.to_be_bytes());
+        raw.extend_from_slice(packet);
+    }
+    raw
+}
+
+/// Writes packets in an Ogg stream, given the granule position of each. The
+/// two first packets, the Opus headers, are alone on their pages.
+fn ogg_stream(packets: &[(Vec<u8>, u64)]) -> Vec<u8> {
+    let mut ogg = Vec::new();
+    let mut sequence = 0;
+    let mut segments: Vec<u8> = Vec::new();
+    let mut body = Vec::new();
+    let mut granule = 0;
+
+    for (index, (packet, packet_granule)) in packets.iter().enumerate() {
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+
+        if !segments.is_empty() && (index <= 2 || segments.len() + lacing.len() > 255) {
+            let header_type = if sequence == 0 { 0x02 } else { 0x00 };
+            ogg_page(&mut ogg, header_type, granule, sequence, &segments, &body);
+            sequence += 1;
+            segments.clear();
+            body.clear();
+        }
+
+        segments.extend(lacing);
+        body.extend_from_slice(packet);
+        granule = *packet_granule;
+    }
+
+    let header_type = if sequence == 0 { 0x06 } else { 0x04 };
+    ogg_page(&mut ogg, header_type, granule, sequence, &segments, &body);
+    ogg
+}
+
+/// Appends an Ogg page of the Opus stream to the output.
+fn ogg_page(
+    ogg: &mut Vec<u8>,
+    header_type: u8,
+    granule: u64,
+    sequence: u32,
+    segments: &[u8],
+    body: &[u8],
+) {
+    let start = ogg.len();
+    ogg.extend_from_slice(b"OggS");
+    ogg.push(0);
+    ogg.push(header_type);
+    ogg.extend_from_slice(&granule.to_le_bytes());
+    ogg.extend_from_slice(&OGG_SERIAL.to_le_bytes());
+    ogg.extend_from_slice(&sequence.to_le_bytes());
+    // The checksum is computed with its own bytes set to 0
+    ogg.extend_from_slice(&0u32.to_le_bytes());
+    ogg.push(segments.len() as u8);
+    ogg.extend_from_slice(segments);
+    ogg.extend_from_slice(body);
+
+    let checksum = ogg_crc(&ogg[start..]);
+    ogg[start + 22..start + 26].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Computes the CRC-32 of Ogg pages (polynomial 0x04C11DB7, not reflected,
+/// without initial value nor final XOR).
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u32) << 24, |crc, _| {
+            if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Returns the packets of an Ogg stream, joining the packets that span several
+/// segments or pages.
+fn ogg_packets(ogg: &[u8]) -> Result<Vec<Vec<u8>>, ExtractError> {
+    let malformed = || ExtractError::UnknownFormat(String::from("malformed Ogg page"));
+
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut offset = 0;
+
+    while offset < ogg.len() {
+        if ogg.get(offset..offset + 4) != Some(b"OggS") {
+            return Err(malformed());
+        }
+        let segment_count = *ogg.get(offset + 26).ok_or_else(malformed)? as usize;
+        let segment_table = ogg
+            .get(offset + 27..offset + 27 + segment_count)
+            .ok_or_else(malformed)?;
+        offset += 27 + segment_count;
+
+        for &segment_len in segment_table {
+            let segment = ogg
+                .get(offset..offset + segment_len as usize)
+                .ok_or_else(malformed)?;
+            offset += segment_len as usize;
+
+            // A packet ends with the first segment shorter than 255 bytes
+            packet.extend_from_slice(segment);
+            if segment_len < 255 {
+                packets.push(std::mem::take(&mut packet));
+            }
+        }
+    }
+
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("Theme.wav", ADPCM, AudioFormat::Wav), "Theme.wav");
+        assert_eq!(
+            file_name("Theme.wav", OPUS, AudioFormat::Native),
+            "Theme.opus"
+        );
+        assert_eq!(file_name("Theme.wav", OPUS, AudioFormat::Wav), "Theme.wav");
+        assert_eq!(
+            file_name("Theme.wav", PCM, AudioFormat::Native),
+            "Theme.wav"
+        );
+        assert_eq!(file_name("Theme.wav", PCM, AudioFormat::Ogg), "Theme.opus");
+        assert_eq!(
+            file_name("Theme.wav", OPUS, AudioFormat::Opus),
+            "Theme.opus.raw"
+        );
+    }
+
+    #[test]
+    fn adpcm_block() {
+        // Mono block with predictor 0, delta 16, samples 100 and 50
+        let mut block = vec![0x00, 16, 0, 100, 0, 50, 0];
+        block.push(0x10);
+
+        let mut samples = Vec::new();
+        decode_adpcm_block(&block, 1, 4, &mut samples);
+
+        assert_eq!(samples, vec![50, 100, 116, 116]);
+    }
+
+    #[test]
+    fn ogg_pages() {
+        let page = |packets: &[&[u8]]| {
+            let mut page = b"OggS".to_vec();
+            page.extend_from_slice(&[0; 22]);
+            page.push(packets.len() as u8);
+            page.extend(packets.iter().map(|packet| packet.len() as u8));
+            packets
+                .iter()
+                .for_each(|packet| page.extend_from_slice(packet));
+            page
+        };
+
+        let mut ogg = page(&[b"OpusHead"]);
+        ogg.extend(page(&[b"OpusTags"]));
+        ogg.extend(page(&[b"ab", b"cd"]));
+
+        assert_eq!(
+            ogg_packets(&ogg).unwrap(),
+            vec![
+                b"OpusHead".to_vec(),
+                b"OpusTags".to_vec(),
+                b"ab".to_vec(),
+                b"cd".to_vec()
+            ]
+        );
+        assert!(ogg_packets(b"nope").is_err());
+    }
+
+    #[test]
+    fn ogg_roundtrip() {
+        let packets = vec![
+            (b"OpusHead".to_vec(), 0),
+            (b"OpusTags".to_vec(), 0),
+            (vec![1; 300], 960),
+            (vec![2; 10], 1920),
+        ];
+        let ogg = ogg_stream(&packets);
+
+        assert_eq!(
+            ogg_packets(&ogg).unwrap(),
+            packets
+                .into_iter()
+                .map(|(packet, _)| packet)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ogg_crc(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn raw_packets() {
+        let raw = raw_opus([&[1, 2][..], &[3][..]].into_iter());
+        assert_eq!(
+            raw,
+            [0, 0, 0, 2, 0, 0, 0, 0, 1, 2, 0, 0, 0, 1, 0, 0, 0, 0, 3]
+        );
+    }
+
+    #[test]
+    fn resampling() {
+        assert_eq!(resample(&[0, 100, 200, 300], 1, 2, 4).len(), 8);
+        assert_eq!(resample(&[0, 100], 1, 1, 2), vec![0, 50, 100, 100]);
+        assert_eq!(resample(&[0, 10, 100, 20], 2, 2, 1), vec![0, 10]);
+    }
+
+    #[test]
+    fn opus_encoding() {
+        let pcm = Pcm {
+            channels: 2,
+            sample_rate: 44_100,
+            samples: vec![0; 44_100 * 2],
+        };
+        let packets = encode_opus(&pcm).unwrap();
+        let (head, _) = &packets[0];
+        let pre_skip = u16::from_le_bytes([head[10], head[11]]) as u64;
+
+        assert!(head.starts_with(b"OpusHead"));
+        assert_eq!(packets.last().unwrap().1, pre_skip + 48_000);
+    }
+
+    #[test]
+    fn pcm_header() {
+        let wav = pcm_wav(2, 48_000, &[1, -1]);
+        assert_eq!(riff_chunk(&wav, b"data").unwrap(), [1, 0, 0xFF, 0xFF]);
+        assert_eq!(
+            read_u32(riff_chunk(&wav, b"fmt ").unwrap(), 4),
+            Some(48_000)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use lotus_lib::cache_pair::{CachePair, CachePairReader};
+use lotus_lib::package::{Package, PackageCollection, PackageType};
+use lotus_lib::toc::{DirectoryNode, Node, NodeKind};
+
+use crate::error::{Error, Result};
+
+/// A `Cache.Windows` directory and the packages it contains.
+pub struct Cache {
+    collection: PackageCollection<CachePairReader>,
+    packages: HashMap<String, Arc<Package<CachePairReader>>>,
+}
+
+impl Cache {
+    /// Opens a `Cache.Windows` directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read.
+    pub fn open<P>(cache_windows_directory: P) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let cache_windows_directory = cache_windows_directory.into();
+
+        let collection = PackageCollection::<CachePairReader>::new(&cache_windows_directory, true)
+            .map_err(|source| Error::Io {
+                path: cache_windows_directory,
+                source,
+            })?;
+
+        Ok(Self {
+            collection,
+            packages: HashMap::new(),
+        })
+    }
+
+    /// Returns the names of the packages of the directory.
+    pub fn package_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .collection
+            .packages()
+            .iter()
+            .map(|package| package.name().clone())
+            .chain(self.packages.keys().cloned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Returns the package with the given name, reading its TOCs on first use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package does not exist or if one of its TOCs
+    /// cannot be read.
+    pub fn package(&mut self, package_name: &str) -> Result<Arc<Package<CachePairReader>>> {
+        if let Some(package) = self.packages.get(package_name) {
+            return Ok(package.clone());
+        }
+
+        let mut package = self
+            .collection
+            .take(package_name)
+            .ok_or_else(|| Error::PackageNotFound(package_name.to_string()))?;
+
+        for package_type in [PackageType::H, PackageType::F, PackageType::B] {
+            if let Some(cache) = package.borrow_mut(package_type) {
+                cache.read_toc()?;
+            }
+        }
+
+        let package = Arc::new(package);
+        self.packages
+            .insert(package_name.to_string(), package.clone());
+
+        Ok(package)
+    }
+}
+
+/// Opens the `Cache.Windows` directory and loads the TOCs of the given package.
+pub fn load_package<P>(
+    cache_windows_directory: P,
+    package_name: &str,
+) -> Result<Arc<Package<CachePairReader>>>
+where
+    P: Into<PathBuf>,
+{
+    Cache::open(cache_windows_directory)?.package(package_name)
+}
+
+/// Lists the content of a directory of the H cache.
+///
+/// Returns the directory node along with its child directories and files, each
+/// sorted by name, or `None` if the path is not an absolute path to a
+/// directory.
+pub fn list_directory<P>(
+    package: &Package<CachePairReader>,
+    path: P,
+) -> Option<(Node, Vec<Node>, Vec<Node>)>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !path.has_root() {
+        return None;
+    }
+
+    let h_cache = package.borrow(PackageType::H)?;
+    let directory = h_cache.get_directory_node(path)?;
+
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+
+    directory.children().into_iter().for_each(|node| {
+        if node.kind() == NodeKind::Directory {
+            directories.push(node);
+        } else {
+            files.push(node);
+        }
+    });
+
+    directories.sort_by_key(|node| node.name());
+    files.sort_by_key(|node| node.name());
+
+    Some((directory, directories, files))
+}
+
+/// Returns the node at the given path of the H cache, be it a directory or a
+/// file.
+///
+/// # Errors
+///
+/// Returns an error if the package has no H cache or if the node does not
+/// exist.
+pub fn get_node<P>(package: &Package<CachePairReader>, path: P) -> Result<Node>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if !path.has_root() {
+        return Err(Error::NodeNotFound(path.to_path_buf()));
+    }
+
+    let h_cache = package
+        .borrow(PackageType::H)
+        .ok_or(Error::CacheNotFound(PackageType::H))?;
+
+    h_cache
+        .get_directory_node(path)
+        .or_else(|| h_cache.get_file_node(path))
+        .ok_or_else(|| Error::NodeNotFound(path.to_path_buf()))
+}
@@ -0,0 +1,35 @@
+use std::io::{stdout, ErrorKind, Write};
+use std::path::Path;
+
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::NodeKind;
+use wfcache_api::extract::{decode_file, read_payload, ExtractOptions};
+
+/// Writes the content of a file to stdout: decoded as it would be extracted,
+/// or the payload of the given cache as is.
+pub fn cat(
+    package: &Package<CachePairReader>,
+    path: &Path,
+    cache: Option<PackageType>,
+    options: &ExtractOptions,
+) -> Result<()> {
+    let node = wfcache_api::get_node(package, path)?;
+    if node.kind() != NodeKind::File {
+        bail!("{}: Is a directory", path.display());
+    }
+
+    let data = match cache {
+        Some(package_type) => read_payload(package, &node, package_type)?,
+        None => decode_file(package, &node, options)?,
+    };
+
+    let mut out = stdout().lock();
+    match out.write_all(&data).and_then(|()| out.flush()) {
+        // The reading end of a pipe may stop early (e.g. `| head`)
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::Package;
+use lotus_lib::toc::NodeKind;
+use tokio_util::sync::CancellationToken;
+use wfcache_api::extract::{
+    collect_files, extract_files, plan_files, ExtractEvent, ExtractOptions, Plan,
+};
+use wfcache_api::sink::ArchiveFormat;
+use wfcache_api::Error;
+
+/// Extracts the given files and directories using `options.jobs` threads,
+/// printing a line per file. The files of the directories are filtered by
+/// `options.filter`.
+///
+/// With `dry_run`, only prints the output path of each file instead.
+///
+/// Failures do not stop the extraction, they are summarized at the end and
+/// turned into an error so that the process exits with a non-zero code.
+pub fn extract(
+    package: &Package<CachePairReader>,
+    paths: &[PathBuf],
+    output_dir: &Path,
+    recursive: bool,
+    dry_run: bool,
+    options: &ExtractOptions,
+) -> Result<()> {
+    let mut failures: Vec<(PathBuf, Error)> = Vec::new();
+    let mut files = Vec::new();
+
+    for path in paths {
+        match wfcache_api::get_node(package, path) {
+            Ok(node) if node.kind() == NodeKind::Directory => {
+                files.extend(
+                    options
+                        .filter
+                        .apply(package, collect_files(&node, recursive)),
+                );
+            }
+            Ok(node) => files.push(node),
+            Err(err) => {
+                eprintln!("{}", err);
+                failures.push((path.clone(), err));
+            }
+        }
+    }
+
+    if dry_run {
+        print_plan(&plan_files(
+            package,
+            &files,
+            output_dir,
+            options,
+            &CancellationToken::new(),
+        ));
+        if !failures.is_empty() {
+            bail!("{} of {} entries not found", failures.len(), paths.len());
+        }
+        return Ok(());
+    }
+
+    if ArchiveFormat::from_path(output_dir).is_some() && output_dir.exists() {
+        eprintln!("Replacing the existing archive {}", output_dir.display());
+    }
+
+    let missing = failures.len();
+    let mut total = files.len();
+    let width = total.to_string().len();
+
+    let mut count = 0;
+    extract_files(
+        package,
+        &files,
+        output_dir,
+        options,
+        &CancellationToken::new(),
+        |event| match event {
+            ExtractEvent::Progress(progress) => {
+                count = progress.count;
+                total = progress.total;
+            }
+            ExtractEvent::Extracted { path, output_path } => println!(
+                "[{:>width$}/{}] {} -> {}",
+                count + 1,
+                total,
+                path.display(),
+                output_path.display()
+            ),
+            ExtractEvent::Skipped { path, output_path } => println!(
+                "[{:>width$}/{}] {} -> {} (skipped)",
+                count + 1,
+                total,
+                path.display(),
+                output_path.display()
+            ),
+            ExtractEvent::Removed { path } => {
+                println!("{} was removed from the package", path.display())
+            }
+            ExtractEvent::Manifest { manifest_path } => {
+                println!("Manifest written to {}", manifest_path.display())
+            }
+            ExtractEvent::Failed { path, error } => {
+                eprintln!(
+                    "[{:>width$}/{}] {}: {}",
+                    count + 1,
+                    total,
+                    path.display(),
+                    error
+                );
+                failures.push((path, error.into()));
+            }
+        },
+    );
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!();
+    eprintln!("Failed to extract {} entries:", failures.len());
+    for (path, err) in &failures {
+        match err {
+            Error::NodeNotFound(_) => eprintln!("  {}", err),
+            _ => eprintln!("  {}: {}", path.display(), err),
+        }
+    }
+
+    bail!(
+        "{} of {} entries failed to extract",
+        failures.len(),
+        total + missing
+    )
+}
+
+/// Prints the files an extraction would write and their total length.
+fn print_plan(plan: &Plan) {
+    for file in &plan.files {
+        match &file.output_path {
+            Ok(output_path) => println!("{} -> {}", file.path.display(), output_path.display()),
+            Err(err) => eprintln!("{}: {}", file.path.display(), err),
+        }
+    }
+    for path in &plan.removed {
+        println!("{} was removed from the package", path.display());
+    }
+
+    println!();
+    println!("{} files, {} bytes", plan.files.len(), plan.total_bytes());
+    if plan.unchanged > 0 {
+        println!("{} unchanged files", plan.unchanged);
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use globset::Glob;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{DirectoryNode, NodeKind};
+
+use crate::args::NodeType;
+
+/// Prints the paths of the entries under a directory, optionally filtered by
+/// name and kind.
+pub fn find(
+    package: &Package<CachePairReader>,
+    path: &Path,
+    name: Option<&str>,
+    kind: Option<NodeType>,
+) -> Result<()> {
+    let h_cache = package
+        .borrow(PackageType::H)
+        .ok_or_else(|| eyre!("No H cache found"))?;
+    let dir_node = h_cache
+        .get_directory_node(path)
+        .ok_or_else(|| eyre!("{}: No such directory", path.display()))?;
+
+    let matcher = name
+        .map(|name| Glob::new(name).map(|glob| glob.compile_matcher()))
+        .transpose()?;
+
+    let mut out = stdout().lock();
+
+    // Walk the tree depth-first, keeping the entries of each directory sorted
+    let mut stack = dir_node.children();
+    stack.sort_by_key(|child| std::cmp::Reverse(child.name()));
+
+    while let Some(node) = stack.pop() {
+        let node_kind = node.kind();
+
+        let kind_matches = match kind {
+            Some(NodeType::File) => node_kind == NodeKind::File,
+            Some(NodeType::Directory) => node_kind == NodeKind::Directory,
+            None => true,
+        };
+        let name_matches = matcher
+            .as_ref()
+            .is_none_or(|matcher| matcher.is_match(node.name()));
+
+        if kind_matches && name_matches {
+            writeln!(out, "{}", node.path().display())?;
+        }
+
+        if node_kind == NodeKind::Directory {
+            let mut children = node.children();
+            children.sort_by_key(|child| std::cmp::Reverse(child.name()));
+            stack.extend(children);
+        }
+    }
+
+    Ok(())
+}
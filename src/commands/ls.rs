@@ -0,0 +1,79 @@
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{FileNode, Node, NodeKind};
+
+/// Prints the content of a directory, or the file itself if the path points
+/// to a file.
+pub fn ls(package: &Package<CachePairReader>, path: &Path, long: bool) -> Result<()> {
+    let nodes = if let Some((_, directories, files)) = wfcache_api::list_directory(package, path) {
+        directories.into_iter().chain(files).collect()
+    } else {
+        let file_node = package
+            .borrow(PackageType::H)
+            .and_then(|h_cache| h_cache.get_file_node(path))
+            .ok_or_else(|| eyre!("{}: No such file or directory", path.display()))?;
+        vec![file_node]
+    };
+
+    let mut out = stdout().lock();
+
+    if long {
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>12} {:>20}  NAME",
+            "H LENGTH", "F LENGTH", "B LENGTH", "TIMESTAMP"
+        )?;
+    }
+
+    for node in nodes {
+        let mut name = node.name();
+        if node.kind() == NodeKind::Directory {
+            name.push('/');
+        }
+
+        if !long {
+            writeln!(out, "{}", name)?;
+            continue;
+        }
+
+        if node.kind() == NodeKind::Directory {
+            writeln!(
+                out,
+                "{:>12} {:>12} {:>12} {:>20}  {}",
+                "-", "-", "-", "-", name
+            )?;
+            continue;
+        }
+
+        let node_path = node.path();
+        let f_node = package
+            .borrow(PackageType::F)
+            .and_then(|f_cache| f_cache.get_file_node(&node_path));
+        let b_node = package
+            .borrow(PackageType::B)
+            .and_then(|b_cache| b_cache.get_file_node(&node_path));
+
+        writeln!(
+            out,
+            "{:>12} {:>12} {:>12} {:>20}  {}",
+            node.len(),
+            length(f_node.as_ref()),
+            length(b_node.as_ref()),
+            node.timestamp(),
+            name
+        )?;
+    }
+
+    Ok(())
+}
+
+#[inline]
+fn length(node: Option<&Node>) -> String {
+    node.map(|node| node.len().to_string())
+        .unwrap_or_else(|| String::from("-"))
+}
@@ -0,0 +1,126 @@
+mod cat;
+mod extract;
+mod find;
+mod ls;
+mod stat;
+mod tree;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::Parser;
+use color_eyre::eyre::{bail, eyre};
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use wfcache_api::extract::ExtractOptions;
+use wfcache_api::Navigator;
+
+use crate::args::{Command, CommandLine};
+
+/// State shared by the commands run outside of the TUI
+pub struct Session {
+    navigator: Navigator,
+    output_dir: PathBuf,
+    extract_options: ExtractOptions,
+}
+
+impl Session {
+    pub fn new<P>(
+        package: Arc<Package<CachePairReader>>,
+        output_dir: P,
+        extract_options: ExtractOptions,
+    ) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        Ok(Self {
+            navigator: Navigator::new(package)?,
+            output_dir: output_dir.into(),
+            extract_options,
+        })
+    }
+
+    #[inline]
+    pub fn package(&self) -> &Arc<Package<CachePairReader>> {
+        self.navigator.package()
+    }
+
+    #[inline]
+    pub fn cwd(&self) -> &Path {
+        self.navigator.cwd()
+    }
+
+    /// Parses and runs a command string (e.g. `ls -l /Lotus`)
+    pub fn execute_line(&mut self, line: &str) -> Result<()> {
+        let words = shlex::split(line).ok_or_else(|| eyre!("Invalid command: {}", line))?;
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        match CommandLine::try_parse_from(words) {
+            Ok(command_line) => self.execute(command_line.command),
+            Err(err) if matches!(err.kind(), ErrorKind::DisplayHelp) => {
+                print!("{}", err.render());
+                Ok(())
+            }
+            Err(err) => bail!("{}", err.render().to_string().trim_end()),
+        }
+    }
+
+    /// Runs a command
+    pub fn execute(&mut self, command: Command) -> Result<()> {
+        let package = self.navigator.package().clone();
+
+        match command {
+            Command::Ls { path, long } => ls::ls(&package, &self.resolve(path), long),
+            Command::Cd { path } => {
+                if !self.navigator.change_directory(&path) {
+                    bail!("{}: No such directory", self.resolve(path).display());
+                }
+                Ok(())
+            }
+            Command::Pwd => {
+                println!("{}", self.cwd().display());
+                Ok(())
+            }
+            Command::Stat { paths } => {
+                let paths: Vec<PathBuf> = paths.into_iter().map(|p| self.resolve(p)).collect();
+                stat::stat(&package, &paths)
+            }
+            Command::Extract {
+                paths,
+                recursive,
+                dry_run,
+            } => {
+                let paths: Vec<PathBuf> = paths.into_iter().map(|p| self.resolve(p)).collect();
+                extract::extract(
+                    &package,
+                    &paths,
+                    &self.output_dir,
+                    recursive,
+                    dry_run,
+                    &self.extract_options,
+                )
+            }
+            Command::Cat { path, raw, cache } => {
+                // A cache without --raw still asks for its payload
+                let cache = cache.map(Into::into).or(raw.then_some(PackageType::H));
+                cat::cat(&package, &self.resolve(path), cache, &self.extract_options)
+            }
+            Command::Find { path, name, kind } => {
+                find::find(&package, &self.resolve(path), name.as_deref(), kind)
+            }
+            Command::Tree { path, depth } => tree::tree(&package, &self.resolve(path), depth),
+        }
+    }
+
+    #[inline]
+    fn resolve<P>(&self, path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        self.navigator.resolve(path)
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{DirectoryNode, FileNode, Node, NodeKind};
+
+/// Prints the cache entries of files and directories, as shown by the Info
+/// widget.
+pub fn stat(package: &Package<CachePairReader>, paths: &[PathBuf]) -> Result<()> {
+    let h_cache = package
+        .borrow(PackageType::H)
+        .ok_or_else(|| eyre!("No H cache found"))?;
+
+    let mut out = stdout().lock();
+
+    for (index, path) in paths.iter().enumerate() {
+        let node = h_cache
+            .get_directory_node(path)
+            .or_else(|| h_cache.get_file_node(path))
+            .ok_or_else(|| eyre!("{}: No such file or directory", path.display()))?;
+
+        if index > 0 {
+            writeln!(out)?;
+        }
+
+        writeln!(out, "Name: {}", node.name())?;
+        writeln!(out, "Path: {}", node.path().display())?;
+
+        if node.kind() == NodeKind::Directory {
+            let children = node.children();
+            let file_count = children
+                .iter()
+                .filter(|child| child.kind() == NodeKind::File)
+                .count();
+
+            writeln!(out, "File count: {}", file_count)?;
+            writeln!(out, "Dir count:  {}", children.len() - file_count)?;
+            continue;
+        }
+
+        for package_type in [PackageType::H, PackageType::F, PackageType::B] {
+            let cache_node = package
+                .borrow(package_type)
+                .and_then(|cache| cache.get_file_node(node.path()));
+
+            if let Some(cache_node) = cache_node {
+                writeln!(out, "{:?} Cache", package_type)?;
+                write_cache_info(&mut out, &cache_node)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_cache_info<W: Write>(out: &mut W, node: &Node) -> Result<()> {
+    writeln!(out, "  Cache offset: {}", node.cache_offset())?;
+    writeln!(out, "  Timestamp:    {}", node.timestamp())?;
+    writeln!(out, "  Comp Length:  {} B", node.comp_len())?;
+    writeln!(out, "  Length:       {} B", node.len())?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::io::{stdout, Write};
+use std::path::Path;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::Package;
+use lotus_lib::toc::Node;
+
+/// Prints a directory and its content as a tree, up to an optional depth.
+pub fn tree(package: &Package<CachePairReader>, path: &Path, depth: Option<usize>) -> Result<()> {
+    wfcache_api::list_directory(package, path)
+        .ok_or_else(|| eyre!("{}: No such directory", path.display()))?;
+
+    let mut out = stdout().lock();
+    writeln!(out, "{}", path.display())?;
+
+    let (directory_count, file_count) = write_tree(&mut out, package, path, "", depth)?;
+
+    writeln!(out)?;
+    writeln!(out, "{} directories, {} files", directory_count, file_count)?;
+
+    Ok(())
+}
+
+fn write_tree<W: Write>(
+    out: &mut W,
+    package: &Package<CachePairReader>,
+    path: &Path,
+    prefix: &str,
+    depth: Option<usize>,
+) -> Result<(usize, usize)> {
+    if depth == Some(0) {
+        return Ok((0, 0));
+    }
+
+    let Some((_, directories, files)) = wfcache_api::list_directory(package, path) else {
+        return Ok((0, 0));
+    };
+
+    let mut directory_count = directories.len();
+    let mut file_count = files.len();
+
+    let nodes: Vec<Node> = directories.into_iter().chain(files).collect();
+    let last_index = nodes.len().saturating_sub(1);
+
+    for (index, node) in nodes.into_iter().enumerate() {
+        let (branch, indent) = if index == last_index {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+
+        writeln!(out, "{}{}{}", prefix, branch, node.name())?;
+
+        let (directories, files) = write_tree(
+            out,
+            package,
+            &node.path(),
+            &format!("{}{}", prefix, indent),
+            depth.map(|depth| depth - 1),
+        )?;
+        directory_count += directories;
+        file_count += files;
+    }
+
+    Ok((directory_count, file_count))
+}
@@ -0,0 +1,79 @@
+use std::io;
+use std::path::PathBuf;
+
+use lotus_lib::package::PackageType;
+
+/// Errors returned by the library.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// An IO operation failed on the given path.
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// No package with the given name exists in the cache directory.
+    #[error("Package {0} not found")]
+    PackageNotFound(String),
+
+    /// The package has no cache of the given type.
+    #[error("No {0:?} cache found")]
+    CacheNotFound(PackageType),
+
+    /// No node exists at the given path.
+    #[error("{}: No such file or directory", .0.display())]
+    NodeNotFound(PathBuf),
+
+    /// A filter expression is invalid.
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+
+    /// A manifest cannot be used to compare files to a previous extraction.
+    #[error("{}: Invalid manifest: {1}", .0.display())]
+    InvalidManifest(PathBuf, String),
+
+    /// A file could not be extracted.
+    #[error(transparent)]
+    Extract(#[from] ExtractError),
+
+    /// The underlying lotus libraries failed to read or decompress data.
+    #[error(transparent)]
+    Lotus(#[from] anyhow::Error),
+}
+
+/// Errors returned when extracting a file.
+#[derive(Debug, thiserror::Error)]
+pub enum ExtractError {
+    /// The data of the file could not be decompressed or decoded.
+    #[error("Failed to decompress: {0}")]
+    Decompression(#[source] anyhow::Error),
+
+    /// The header of the file describes a format that is not supported.
+    #[error("Unknown format: {0}")]
+    UnknownFormat(String),
+
+    /// The file could not be converted to the requested format.
+    #[error("Failed to convert: {0}")]
+    Conversion(String),
+
+    /// The file could not be written to the output directory.
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The file has no entry in a cache pair its format requires.
+    #[error("Missing {0:?} cache entry")]
+    MissingCache(PackageType),
+
+    /// The extraction of the file panicked, usually on malformed data.
+    #[error("Extraction panicked: {0}")]
+    Panic(String),
+}
+
+/// Result type of the library.
+pub type Result<T> = std::result::Result<T, Error>;
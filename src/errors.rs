@@ -12,6 +12,10 @@ pub fn install_hooks() -> color_eyre::Result<()> {
     // convert from a color_eyre PanicHook to a standard panic hook
     let panic_hook = panic_hook.into_panic_hook();
     panic::set_hook(Box::new(move |panic_info| {
+        // Panics caught by the extraction are reported as failed files
+        if wfcache_api::extract::catches_panics() {
+            return;
+        }
         tui::restore().unwrap();
         panic_hook(panic_info);
     }));
@@ -27,3 +31,23 @@ pub fn install_hooks() -> color_eyre::Result<()> {
 
     Ok(())
 }
+
+/// This installs the standard color_eyre panic and error hooks for the
+/// commands run outside of the TUI, without printing the panics caught by the
+/// extraction.
+pub fn install_headless_hooks() -> color_eyre::Result<()> {
+    let (panic_hook, eyre_hook) = HookBuilder::default().into_hooks();
+
+    let panic_hook = panic_hook.into_panic_hook();
+    panic::set_hook(Box::new(move |panic_info| {
+        // Panics caught by the extraction are reported as failed files
+        if wfcache_api::extract::catches_panics() {
+            return;
+        }
+        panic_hook(panic_info);
+    }));
+
+    eyre_hook.install()?;
+
+    Ok(())
+}
@@ -1,66 +1,532 @@
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::{Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::cache_pair::{CachePair, CachePairReader};
+use lotus_lib::compression::{decompress_post_ensmallening, get_block_lengths};
 use lotus_lib::package::{Package, PackageType};
-use lotus_lib::toc::{DirectoryNode, Node, NodeKind};
-use lotus_utils_audio::Audio;
-use lotus_utils_texture::Texture;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use lotus_lib::toc::{DirectoryNode, FileNode, Node, NodeKind};
+use tokio_util::sync::CancellationToken;
 
+use crate::error::ExtractError;
+use crate::filter::Filter;
+use crate::format::{FileContext, FormatHandler, Registry};
+use crate::manifest::{self, Manifest, ManifestEntry, ManifestFormat};
+use crate::sink::{self, Sink};
+
+/// Policy deciding whether an existing output file is overwritten.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OverwritePolicy {
+    /// Always write the file
+    #[default]
+    Always,
+    /// Never overwrite an existing file
+    Never,
+    /// Overwrite the file if the cache entry is newer than it
+    IfNewer,
+    /// Overwrite the file if its length differs from the extracted data
+    IfSizeDiffers,
+}
+
+impl OverwritePolicy {
+    /// Returns the next policy, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Always => Self::Never,
+            Self::Never => Self::IfNewer,
+            Self::IfNewer => Self::IfSizeDiffers,
+            Self::IfSizeDiffers => Self::Always,
+        }
+    }
+}
+
+impl fmt::Display for OverwritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Always => write!(f, "Always"),
+            Self::Never => write!(f, "Never"),
+            Self::IfNewer => write!(f, "If Newer"),
+            Self::IfSizeDiffers => write!(f, "If Size Differs"),
+        }
+    }
+}
+
+/// Format textures are exported in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    /// PNG image decoded from the DDS file
+    #[default]
+    Png,
+    /// TGA image decoded from the DDS file
+    Tga,
+    /// DDS file as stored in the cache
+    Dds,
+}
+
+impl TextureFormat {
+    /// Returns the next format, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Png => Self::Tga,
+            Self::Tga => Self::Dds,
+            Self::Dds => Self::Png,
+        }
+    }
+}
+
+impl fmt::Display for TextureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Png => write!(f, "PNG"),
+            Self::Tga => write!(f, "TGA"),
+            Self::Dds => write!(f, "DDS"),
+        }
+    }
+}
+
+/// Format audio files are exported in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AudioFormat {
+    /// WAV for PCM and ADPCM sources, Opus in Ogg for Opus sources
+    #[default]
+    Native,
+    /// 16-bit PCM WAV, decoding ADPCM and Opus sources
+    Wav,
+    /// Opus packets with the framing of `opus_demo`, encoding PCM and ADPCM
+    /// sources
+    Opus,
+    /// Opus in Ogg, encoding PCM and ADPCM sources
+    Ogg,
+}
+
+impl AudioFormat {
+    /// Returns the next format, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Native => Self::Wav,
+            Self::Wav => Self::Opus,
+            Self::Opus => Self::Ogg,
+            Self::Ogg => Self::Native,
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Native => write!(f, "Native"),
+            Self::Wav => write!(f, "WAV"),
+            Self::Opus => write!(f, "Raw Opus"),
+            Self::Ogg => write!(f, "Ogg Opus"),
+        }
+    }
+}
+
+/// Options of an extraction.
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// Number of files extracted in parallel
+    pub jobs: usize,
+    /// Policy applied to the output files that already exist
+    pub overwrite: OverwritePolicy,
+    /// Format of the manifest written in the output directory, if any
+    pub manifest: Option<ManifestFormat>,
+    /// Previous manifest, only the files that are new or changed since are
+    /// extracted
+    pub since_manifest: Option<Arc<Manifest>>,
+    /// Filter on the files collected from directories
+    pub filter: Filter,
+    /// Write the decompressed H payload and the raw F and B payloads of each
+    /// file side by side (e.g. `file.png.h`, `file.png.f`, `file.png.b`)
+    /// instead of converting them
+    pub raw: bool,
+    /// Format audio files are exported in
+    pub audio: AudioFormat,
+    /// Format textures are exported in
+    pub texture: TextureFormat,
+    /// Also write the DDS file of the textures exported as PNG or TGA, next to
+    /// the image
+    pub keep_dds: bool,
+    /// Handlers deciding how the files are decoded
+    pub handlers: Arc<Registry>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            overwrite: OverwritePolicy::default(),
+            manifest: None,
+            since_manifest: None,
+            filter: Filter::default(),
+            raw: false,
+            audio: AudioFormat::default(),
+            texture: TextureFormat::default(),
+            keep_dds: false,
+            handlers: Arc::new(Registry::default()),
+        }
+    }
+}
+
+/// Result of the extraction of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The file was written to the output path.
+    Written(PathBuf),
+    /// The output path already existed and was kept as is.
+    Skipped(PathBuf),
+}
+
+impl Outcome {
+    #[inline]
+    pub fn output_path(&self) -> &Path {
+        match self {
+            Self::Written(output_path) | Self::Skipped(output_path) => output_path,
+        }
+    }
+}
+
+/// File an extraction would write, see [`plan_files`].
+#[derive(Debug)]
+pub struct PlannedFile {
+    /// TOC path of the file
+    pub path: PathBuf,
+    /// Path the file would be written to
+    pub output_path: Result<PathBuf, ExtractError>,
+    /// Uncompressed length of the file, see [`entry_len`]
+    pub len: u64,
+}
+
+/// Files an extraction would write, computed without extracting anything.
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub files: Vec<PlannedFile>,
+    /// Number of files left out as unchanged since the previous manifest
+    pub unchanged: usize,
+    /// Files of the previous manifest that are no longer in the package
+    pub removed: Vec<PathBuf>,
+}
+
+impl Plan {
+    /// Returns the uncompressed length of the files.
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.len).sum()
+    }
+}
+
+/// Progress of an extraction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of files processed, successfully or not
+    pub count: usize,
+    /// Number of files to process
+    pub total: usize,
+    /// Number of files that failed to extract
+    pub failed: usize,
+    /// Number of files kept as is because of the overwrite policy
+    pub skipped: usize,
+    /// Number of files left out as unchanged since the previous manifest
+    pub unchanged: usize,
+    /// Uncompressed length of the files processed, see [`entry_len`]
+    pub bytes: u64,
+    /// Uncompressed length of the files to process
+    pub total_bytes: u64,
+    /// Time elapsed since the extraction started
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.count == self.total
+    }
+
+    /// Returns the ratio of processed bytes, or of processed files when the
+    /// files are empty.
+    pub fn ratio(&self) -> f64 {
+        if self.total_bytes > 0 {
+            self.bytes as f64 / self.total_bytes as f64
+        } else if self.total > 0 {
+            self.count as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the number of bytes processed per second.
+    pub fn throughput(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed > 0.0 {
+            self.bytes as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the estimated time remaining, or `None` until some bytes were
+    /// processed.
+    pub fn eta(&self) -> Option<Duration> {
+        let throughput = self.throughput();
+        if throughput > 0.0 {
+            let remaining = self.total_bytes.saturating_sub(self.bytes);
+            Some(Duration::from_secs_f64(remaining as f64 / throughput))
+        } else {
+            None
+        }
+    }
+}
+
+/// Event sent while extracting a directory.
+#[derive(Debug)]
+pub enum ExtractEvent {
+    /// The progress changed.
+    Progress(Progress),
+
+    /// A file was extracted to the output path.
+    Extracted { path: PathBuf, output_path: PathBuf },
+
+    /// A file was not extracted because its output path already existed.
+    Skipped { path: PathBuf, output_path: PathBuf },
+
+    /// A file of the previous manifest is no longer in the package.
+    Removed { path: PathBuf },
+
+    /// The manifest was written to the given path.
+    Manifest { manifest_path: PathBuf },
+
+    /// A file failed to extract.
+    Failed { path: PathBuf, error: ExtractError },
+}
+
+/// Extracts a file node into the output directory, mirroring its TOC path.
+///
+/// The output directory may also be the path of an archive, see
+/// [`sink::open`]. Existing output files are only overwritten as allowed by
+/// the overwrite policy of the options. In raw mode, the returned output path
+/// is the one of the H payload, the F and B payloads are written next to it.
+///
+/// An archive is created anew on each call, replacing the previous one: to
+/// write several files into an archive, open it once and use
+/// [`extract_file_to`].
 pub fn extract_file(
     package: &Package<CachePairReader>,
     file_node: &Node,
-    output_dir: &PathBuf,
-    count: usize,
-    total: usize,
-    progress_tx: UnboundedSender<(usize, usize)>,
-) {
-    progress_tx.send((count, total)).unwrap();
-
-    let file_name = file_node.name();
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Outcome, ExtractError> {
+    let sink = sink::open(output_dir)?;
+    let outcome = extract_file_to(package, file_node, output_dir, sink.as_ref(), options)?;
+    sink.finish()?;
+    Ok(outcome)
+}
+
+/// Extracts a file node into a sink whose root is the output directory, see
+/// [`extract_file`].
+pub fn extract_file_to(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    output_dir: &Path,
+    sink: &dyn Sink,
+    options: &ExtractOptions,
+) -> Result<Outcome, ExtractError> {
+    guarded_write_file(package, file_node, output_dir, sink, options).map(|(outcome, _)| outcome)
+}
+
+thread_local! {
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns whether a panic on the current thread is caught by the extraction
+/// and reported as an [`ExtractError::Panic`], so that panic hooks can tell it
+/// apart from a crash.
+pub fn catches_panics() -> bool {
+    CATCHING_PANICS.get()
+}
+
+/// Extracts a file node with [`write_file`], turning a panic of the format
+/// libraries into an error so that it does not abort the other files.
+fn guarded_write_file(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    output_dir: &Path,
+    sink: &dyn Sink,
+    options: &ExtractOptions,
+) -> Result<(Outcome, Option<String>), ExtractError> {
+    let catching = CATCHING_PANICS.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        write_file(package, file_node, output_dir, sink, options)
+    }));
+    CATCHING_PANICS.set(catching);
+
+    result.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown panic"));
+        Err(ExtractError::Panic(message))
+    })
+}
+
+/// Extracts a file node into a sink, also returning the SHA-256 of the
+/// output file when a manifest is requested.
+fn write_file(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    output_dir: &Path,
+    sink: &dyn Sink,
+    options: &ExtractOptions,
+) -> Result<(Outcome, Option<String>), ExtractError> {
     let file_path = file_node.path();
 
-    let output_dir = if let Some(parent) = file_path.strip_prefix("/").unwrap().parent() {
-        let mut output_dir = output_dir.clone();
-        output_dir.push(parent);
-        output_dir
-    } else {
-        output_dir.clone()
+    // Find out the output path before decompressing anything so that existing
+    // files can be skipped cheaply
+    let (file, format, output_path) = resolve_output(package, file_node, output_dir, options)?;
+    let relative_path = output_path
+        .strip_prefix(output_dir)
+        .unwrap_or(&output_path)
+        .to_path_buf();
+    let metadata = sink.metadata(&relative_path);
+
+    let skipped = |output_path| {
+        let sha256 = options
+            .manifest
+            .and_then(|_| sink.read(&relative_path))
+            .map(|data| manifest::sha256(&data));
+        Ok((Outcome::Skipped(output_path), sha256))
     };
-    std::fs::create_dir_all(&output_dir).unwrap();
 
-    let mut file_name: String = file_name;
-    let file_data: Vec<u8>;
+    if let Some(metadata) = &metadata {
+        let skip = match options.overwrite {
+            OverwritePolicy::Always | OverwritePolicy::IfSizeDiffers => false,
+            OverwritePolicy::Never => true,
+            OverwritePolicy::IfNewer => is_up_to_date(package, &file_path, metadata),
+        };
+        if skip {
+            return skipped(output_path);
+        }
+    }
 
-    if package.is_texture(file_node).unwrap() {
-        (file_data, file_name) = package.decompress_texture(file_node).unwrap();
-    } else if package.is_audio(file_node).unwrap() {
-        (file_data, file_name) = package.decompress_audio(file_node).unwrap();
-    } else {
-        // Decompress and extract a file from the cache without parsing it (e.g. audio, texture)
-        let cache = package.borrow(PackageType::H).unwrap();
-        let file_node = cache.get_file_node(&file_path).unwrap();
-        file_data = cache.decompress_data(file_node.clone()).unwrap()
+    let (file_data, extras) = match format {
+        Format::Handler(handler) => handler.decode_with_extras(&file, options)?,
+        // Decompress the H payload without parsing it
+        Format::Payloads => (decompress_header(package, file.node())?, Vec::new()),
+    };
+
+    if options.overwrite == OverwritePolicy::IfSizeDiffers
+        && metadata.is_some_and(|metadata| metadata.len() == file_data.len() as u64)
+    {
+        return skipped(output_path);
     }
 
     // Write the file
-    std::fs::write(output_dir.join(file_name), file_data).unwrap();
+    sink.write(&relative_path, &file_data)?;
+    for (name, data) in &extras {
+        sink.write(&relative_path.with_file_name(name), data)?;
+    }
+
+    if matches!(format, Format::Payloads) {
+        write_payloads(package, file_node, &relative_path, sink)?;
+    }
 
-    progress_tx.send((count + 1, total)).unwrap();
+    let sha256 = options.manifest.map(|_| manifest::sha256(&file_data));
+    Ok((Outcome::Written(output_path), sha256))
 }
 
-pub fn extract_dir(
+/// Decodes a file node with the format handler that would extract it,
+/// returning the content of the output file.
+pub fn decode_file(
     package: &Package<CachePairReader>,
-    dir_node: &Node,
-    output_dir: &PathBuf,
-    recursive: bool,
-    extract_rx: &mut UnboundedReceiver<()>,
-    progress_tx: UnboundedSender<(usize, usize)>,
-) {
+    file_node: &Node,
+    options: &ExtractOptions,
+) -> Result<Vec<u8>, ExtractError> {
+    let file = FileContext::new(package, cache_node(package, PackageType::H, file_node)?);
+    options.handlers.find(&file)?.decode(&file, options)
+}
+
+/// Returns the payload of a file node in a cache: decompressed for the H
+/// cache, as stored for the F and B caches.
+pub fn read_payload(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    package_type: PackageType,
+) -> Result<Vec<u8>, ExtractError> {
+    let node = cache_node(package, package_type, file_node)?;
+    match package_type {
+        PackageType::H => decompress_header(package, &node),
+        PackageType::F | PackageType::B => package
+            .borrow(package_type)
+            .ok_or(ExtractError::MissingCache(package_type))?
+            .get_data(node)
+            .map_err(ExtractError::Decompression),
+    }
+}
+
+/// Returns the first `len` bytes of the decompressed payload of a file node in
+/// a cache, and the length of the whole payload.
+pub fn read_payload_prefix(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    package_type: PackageType,
+    len: usize,
+) -> Result<(Vec<u8>, usize), ExtractError> {
+    let node = cache_node(package, package_type, file_node)?;
+    let mut data = decompress_prefix(package, package_type, &node, len)?;
+    data.truncate(len);
+    Ok((data, node.len().max(0) as usize))
+}
+
+/// Returns the path a file node would be extracted to, without decompressing
+/// more than the headers needed to find out its format.
+pub fn output_path(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<PathBuf, ExtractError> {
+    resolve_output(package, file_node, output_dir, options).map(|(_, _, output_path)| output_path)
+}
+
+/// Returns the uncompressed length of a file, summed over the H, F and B
+/// cache entries sharing its path.
+pub fn entry_len(package: &Package<CachePairReader>, file_node: &Node) -> u64 {
+    let file_path = file_node.path();
+
+    [PackageType::H, PackageType::F, PackageType::B]
+        .into_iter()
+        .filter_map(|package_type| package.borrow(package_type))
+        .filter_map(|cache| cache.get_file_node(&file_path))
+        .map(|node| node.len().max(0) as u64)
+        .sum()
+}
+
+/// Returns the most recent timestamp of the H, F and B cache entries sharing
+/// the path of a file.
+pub fn entry_timestamp(package: &Package<CachePairReader>, file_node: &Node) -> Option<SystemTime> {
+    let file_path = file_node.path();
+
+    [PackageType::H, PackageType::F, PackageType::B]
+        .into_iter()
+        .filter_map(|package_type| package.borrow(package_type))
+        .filter_map(|cache| cache.get_file_node(&file_path))
+        .filter_map(|node| timestamp_to_system_time(node.timestamp()))
+        .max()
+}
+
+/// Collects the file nodes of a directory, including the ones of its
+/// subdirectories if `recursive` is set.
+pub fn collect_files(dir_node: &Node, recursive: bool) -> Vec<Node> {
     let mut files: Vec<Node> = Vec::new();
     let mut directories: Vec<Node> = vec![dir_node.clone()];
 
-    while directories.len() > 0 {
+    while !directories.is_empty() {
         let directory = directories.remove(0);
         for child_node in directory.children() {
             if child_node.kind() == NodeKind::Directory && recursive {
@@ -71,20 +537,532 @@ pub fn extract_dir(
         }
     }
 
-    let total = files.len();
+    files
+}
+
+/// Extracts files using `options.jobs` worker threads, calling `on_event`
+/// with the result of each file and the progress after it.
+///
+/// Files that fail to extract are skipped. The extraction stops early when the
+/// cancellation token is cancelled. If requested by the options, a manifest of
+/// the processed files is written in the output directory at the end. Returns
+/// the final progress.
+///
+/// With a previous manifest, only the files that are new or changed since are
+/// extracted, and the files that were removed from the package are reported.
+///
+/// The output directory may also be the path of an archive, see
+/// [`sink::open`], which is finished once the files are written. An archive is
+/// created anew on each call, replacing the previous one: to extract into an
+/// archive several times, open it once and use [`extract_files_to`].
+pub fn extract_files<F>(
+    package: &Package<CachePairReader>,
+    files: &[Node],
+    output_dir: &Path,
+    options: &ExtractOptions,
+    cancellation_token: &CancellationToken,
+    mut on_event: F,
+) -> Progress
+where
+    F: FnMut(ExtractEvent),
+{
+    let sink = match sink::open(output_dir) {
+        Ok(sink) => sink,
+        Err(error) => {
+            on_event(ExtractEvent::Failed {
+                path: output_dir.to_path_buf(),
+                error,
+            });
+            return Progress::default();
+        }
+    };
+
+    let progress = extract_files_to(
+        package,
+        files,
+        output_dir,
+        sink.as_ref(),
+        options,
+        cancellation_token,
+        &mut on_event,
+    );
+
+    if let Err(error) = sink.finish() {
+        on_event(ExtractEvent::Failed {
+            path: output_dir.to_path_buf(),
+            error,
+        });
+    }
+
+    progress
+}
+
+/// Extracts files into a sink whose root is the output directory, see
+/// [`extract_files`]. The sink is not finished.
+pub fn extract_files_to<F>(
+    package: &Package<CachePairReader>,
+    files: &[Node],
+    output_dir: &Path,
+    sink: &dyn Sink,
+    options: &ExtractOptions,
+    cancellation_token: &CancellationToken,
+    mut on_event: F,
+) -> Progress
+where
+    F: FnMut(ExtractEvent),
+{
+    let started = Instant::now();
+
+    let changes = options
+        .since_manifest
+        .as_deref()
+        .map(|previous| previous.changes(package, files));
+    let files = match &changes {
+        Some(changes) => &changes.files[..],
+        None => files,
+    };
+
+    let lens: Vec<u64> = files
+        .iter()
+        .map(|file_node| entry_len(package, file_node))
+        .collect();
+
+    let mut progress = Progress {
+        total: files.len(),
+        total_bytes: lens.iter().sum(),
+        unchanged: changes
+            .as_ref()
+            .map_or(0, |changes| changes.unchanged.len()),
+        ..Default::default()
+    };
+
+    for entry in changes.iter().flat_map(|changes| &changes.removed) {
+        on_event(ExtractEvent::Removed {
+            path: PathBuf::from(&entry.path),
+        });
+    }
+
+    on_event(ExtractEvent::Progress(progress));
+
+    let mut manifest = Manifest::default();
+    let next_index = AtomicUsize::new(0);
+    let (result_tx, result_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.clamp(1, files.len().max(1)) {
+            let next_index = &next_index;
+            let result_tx = result_tx.clone();
+
+            scope.spawn(move || {
+                while !cancellation_token.is_cancelled() {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    let Some(file_node) = files.get(index) else {
+                        break;
+                    };
+
+                    let result = guarded_write_file(package, file_node, output_dir, sink, options);
+                    let (result, manifest_entry) = match result {
+                        Ok((outcome, sha256)) => {
+                            let manifest_entry = sha256.map(|sha256| {
+                                ManifestEntry::new(
+                                    package,
+                                    file_node,
+                                    output_dir,
+                                    outcome.output_path(),
+                                    sha256,
+                                )
+                            });
+                            (Ok(outcome), manifest_entry)
+                        }
+                        Err(error) => (Err(error), None),
+                    };
+
+                    let message = (index, file_node.path(), result, manifest_entry);
+                    if result_tx.send(message).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        // Report the results from the calling thread as the workers finish
+        for (index, path, result, manifest_entry) in result_rx {
+            let event = match result {
+                Ok(Outcome::Written(output_path)) => ExtractEvent::Extracted { path, output_path },
+                Ok(Outcome::Skipped(output_path)) => {
+                    progress.skipped += 1;
+                    ExtractEvent::Skipped { path, output_path }
+                }
+                Err(error) => {
+                    progress.failed += 1;
+                    ExtractEvent::Failed { path, error }
+                }
+            };
+            progress.count += 1;
+            progress.bytes += lens[index];
+            progress.elapsed = started.elapsed();
+
+            manifest.entries.extend(manifest_entry);
+
+            on_event(event);
+            on_event(ExtractEvent::Progress(progress));
+        }
+    });
 
-    for (count, file_node) in files.iter().enumerate() {
-        if extract_rx.try_recv().is_ok() {
-            break;
+    if let Some(manifest_format) = options.manifest {
+        manifest.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        if let (Some(previous), Some(changes)) = (&options.since_manifest, &changes) {
+            manifest.carry_over(previous, package.name(), changes);
+        } else if let Some(existing) = existing_manifest(sink, manifest_format) {
+            manifest.merge(&existing);
         }
 
-        extract_file(
-            package,
-            file_node,
-            &output_dir,
-            count,
-            total,
-            progress_tx.clone(),
+        let manifest_path = output_dir.join(manifest_format.file_name());
+        let result = manifest
+            .to_bytes(manifest_format)
+            .map_err(|source| ExtractError::Io {
+                path: manifest_path.clone(),
+                source,
+            })
+            .and_then(|data| sink.write_summary(Path::new(manifest_format.file_name()), &data));
+
+        on_event(match result {
+            Ok(()) => ExtractEvent::Manifest { manifest_path },
+            Err(error) => ExtractEvent::Failed {
+                path: manifest_path,
+                error,
+            },
+        });
+    }
+
+    progress
+}
+
+/// Reads the manifest written in the sink by a previous extraction, only JSON
+/// manifests can be read back, from directories and from archives still being
+/// written.
+fn existing_manifest(sink: &dyn Sink, format: ManifestFormat) -> Option<Manifest> {
+    if format != ManifestFormat::Json {
+        return None;
+    }
+    let data = sink.read(Path::new(format.file_name()))?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Extracts the files of a directory matching the filter of the options, see
+/// [`extract_files`].
+pub fn extract_dir<F>(
+    package: &Package<CachePairReader>,
+    dir_node: &Node,
+    output_dir: &Path,
+    recursive: bool,
+    options: &ExtractOptions,
+    cancellation_token: &CancellationToken,
+    on_event: F,
+) -> Progress
+where
+    F: FnMut(ExtractEvent),
+{
+    let files = options
+        .filter
+        .apply(package, collect_files(dir_node, recursive));
+    extract_files(
+        package,
+        &files,
+        output_dir,
+        options,
+        cancellation_token,
+        on_event,
+    )
+}
+
+/// Finds out the context given to the format handlers, format and output
+/// path of a file node.
+fn resolve_output<'p, 'a>(
+    package: &'p Package<CachePairReader>,
+    file_node: &Node,
+    output_dir: &Path,
+    options: &'a ExtractOptions,
+) -> Result<(FileContext<'p>, Format<'a>, PathBuf), ExtractError> {
+    let file_path = file_node.path();
+
+    let output_dir = match file_path.strip_prefix("/").ok().and_then(Path::parent) {
+        Some(parent) => output_dir.join(parent),
+        None => output_dir.to_path_buf(),
+    };
+
+    let h_cache = package
+        .borrow(PackageType::H)
+        .ok_or(ExtractError::MissingCache(PackageType::H))?;
+    let h_node = h_cache
+        .get_file_node(&file_path)
+        .ok_or(ExtractError::MissingCache(PackageType::H))?;
+
+    let file = FileContext::new(package, h_node);
+    let (format, file_name) = if options.raw {
+        (
+            Format::Payloads,
+            payload_file_name(&file_node.name(), PackageType::H),
+        )
+    } else {
+        let handler = options.handlers.find(&file)?;
+        (
+            Format::Handler(handler),
+            handler.output_name(&file, options)?,
+        )
+    };
+
+    let output_path = output_dir.join(file_name);
+
+    Ok((file, format, output_path))
+}
+
+/// Computes what [`extract_files`] would write for the same files and options,
+/// without decompressing more than the headers needed to name the outputs.
+///
+/// Stops early, leaving out the remaining files, once the cancellation token
+/// is cancelled.
+pub fn plan_files(
+    package: &Package<CachePairReader>,
+    files: &[Node],
+    output_dir: &Path,
+    options: &ExtractOptions,
+    cancellation_token: &CancellationToken,
+) -> Plan {
+    let changes = options
+        .since_manifest
+        .as_deref()
+        .map(|previous| previous.changes(package, files));
+
+    let mut plan = Plan::default();
+    let files = match &changes {
+        Some(changes) => {
+            plan.unchanged = changes.unchanged.len();
+            plan.removed = changes
+                .removed
+                .iter()
+                .map(|entry| PathBuf::from(&entry.path))
+                .collect();
+            &changes.files[..]
+        }
+        None => files,
+    };
+
+    plan.files = files
+        .iter()
+        .take_while(|_| !cancellation_token.is_cancelled())
+        .map(|file_node| PlannedFile {
+            path: file_node.path(),
+            output_path: output_path(package, file_node, output_dir, options),
+            len: entry_len(package, file_node),
+        })
+        .collect();
+
+    plan
+}
+
+/// Format of a file, deciding how it is decompressed.
+#[derive(Clone, Copy)]
+enum Format<'a> {
+    /// File decoded by a format handler
+    Handler(&'a dyn FormatHandler),
+    /// Payloads of the H, F and B caches, written side by side
+    Payloads,
+}
+
+/// Returns the name of the payload of a cache (e.g. `file.png.f`).
+fn payload_file_name(name: &str, package_type: PackageType) -> String {
+    let extension = match package_type {
+        PackageType::H => "h",
+        PackageType::F => "f",
+        PackageType::B => "b",
+    };
+    format!("{}.{}", name, extension)
+}
+
+/// Writes the raw F and B cache payloads of a file next to its H payload.
+fn write_payloads(
+    package: &Package<CachePairReader>,
+    file_node: &Node,
+    h_output_path: &Path,
+    sink: &dyn Sink,
+) -> Result<(), ExtractError> {
+    for package_type in [PackageType::F, PackageType::B] {
+        if cache_node(package, package_type, file_node).is_err() {
+            continue;
+        }
+
+        let data = read_payload(package, file_node, package_type)?;
+        let output_path =
+            h_output_path.with_file_name(payload_file_name(&file_node.name(), package_type));
+        sink.write(&output_path, &data)?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether an existing output file is at least as recent as the cache
+/// entries of a file.
+fn is_up_to_date(
+    package: &Package<CachePairReader>,
+    file_path: &Path,
+    metadata: &Metadata,
+) -> bool {
+    let Some(h_node) = package
+        .borrow(PackageType::H)
+        .and_then(|cache| cache.get_file_node(file_path))
+    else {
+        return false;
+    };
+
+    match (entry_timestamp(package, &h_node), metadata.modified()) {
+        (Some(timestamp), Ok(modified)) => modified >= timestamp,
+        _ => false,
+    }
+}
+
+/// Converts a TOC timestamp, a Windows file time counting 100 ns intervals
+/// since 1601, to a system time.
+fn timestamp_to_system_time(timestamp: i64) -> Option<SystemTime> {
+    const UNIX_EPOCH_FILE_TIME: i64 = 116_444_736_000_000_000;
+
+    let intervals = u64::try_from(timestamp.checked_sub(UNIX_EPOCH_FILE_TIME)?).ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_nanos(intervals.checked_mul(100)?))
+}
+
+/// Decompresses the H cache payload of a file.
+#[inline]
+pub(crate) fn decompress_header(
+    package: &Package<CachePairReader>,
+    node: &Node,
+) -> Result<Vec<u8>, ExtractError> {
+    let h_cache = package
+        .borrow(PackageType::H)
+        .ok_or(ExtractError::MissingCache(PackageType::H))?;
+    h_cache
+        .decompress_data(node.clone())
+        .map_err(ExtractError::Decompression)
+}
+
+/// Decompresses the start of the payload of a file node in a cache, at least
+/// `len` bytes of it unless the payload is shorter.
+///
+/// Only the blocks holding them are decompressed when the cache stores the
+/// payload in blocks, the whole payload is decompressed otherwise.
+pub(crate) fn decompress_prefix(
+    package: &Package<CachePairReader>,
+    package_type: PackageType,
+    node: &Node,
+    len: usize,
+) -> Result<Vec<u8>, ExtractError> {
+    let cache = package
+        .borrow(package_type)
+        .ok_or(ExtractError::MissingCache(package_type))?;
+    let decompress_all = || {
+        cache
+            .decompress_data(node.clone())
+            .map_err(ExtractError::Decompression)
+    };
+    if node.comp_len() == node.len()
+        || !cache.is_post_ensmallening()
+        || len >= node.len().max(0) as usize
+    {
+        return decompress_all();
+    }
+
+    let read_blocks = || -> anyhow::Result<Option<Vec<u8>>> {
+        let mut cache_reader = File::open(cache.cache_path())?;
+        cache_reader.seek(SeekFrom::Start(node.cache_offset() as u64))?;
+
+        let mut data = Vec::new();
+        while data.len() < len {
+            let Some((block_comp_len, block_decomp_len)) = get_block_lengths(&mut cache_reader)?
+            else {
+                // The payload is a single block without its lengths
+                return Ok(None);
+            };
+            if block_decomp_len == 0 {
+                return Ok(None);
+            }
+
+            // Decompress the block alone, reading its lengths again
+            cache_reader.seek(SeekFrom::Current(-8))?;
+            data.extend(decompress_post_ensmallening(
+                block_comp_len,
+                block_decomp_len,
+                &mut cache_reader,
+            )?);
+        }
+        Ok(Some(data))
+    };
+
+    match read_blocks().map_err(ExtractError::Decompression)? {
+        Some(data) => Ok(data),
+        None => decompress_all(),
+    }
+}
+
+/// Returns the node sharing the path of a file in another cache.
+#[inline]
+pub(crate) fn cache_node(
+    package: &Package<CachePairReader>,
+    package_type: PackageType,
+    node: &Node,
+) -> Result<Node, ExtractError> {
+    package
+        .borrow(package_type)
+        .and_then(|cache| cache.get_file_node(node.path()))
+        .ok_or(ExtractError::MissingCache(package_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_eta() {
+        let progress = Progress {
+            count: 1,
+            total: 4,
+            bytes: 1_000,
+            total_bytes: 4_000,
+            elapsed: Duration::from_secs(2),
+            ..Default::default()
+        };
+
+        assert_eq!(progress.ratio(), 0.25);
+        assert_eq!(progress.throughput(), 500.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(payload_file_name("Icon.png", PackageType::F), "Icon.png.f");
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            timestamp_to_system_time(116_444_736_000_000_000),
+            Some(UNIX_EPOCH)
+        );
+        assert_eq!(
+            timestamp_to_system_time(116_444_736_010_000_000),
+            Some(UNIX_EPOCH + Duration::from_secs(1))
         );
+        assert_eq!(timestamp_to_system_time(0), None);
+    }
+
+    #[test]
+    fn progress_empty_files() {
+        let progress = Progress {
+            count: 1,
+            total: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(progress.ratio(), 0.5);
+        assert_eq!(progress.eta(), None);
     }
 }
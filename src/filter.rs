@@ -0,0 +1,238 @@
+use std::fmt;
+
+use globset::{Glob, GlobMatcher};
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::Package;
+use lotus_lib::toc::Node;
+use regex::Regex;
+
+use crate::error::{Error, Result};
+use crate::extract::entry_len;
+
+/// Filter on the files of an extraction, by TOC path and by length.
+///
+/// A file matches if its path matches any of the include globs or regexes (if
+/// any), none of the exclude globs, and if its length is within the size
+/// bounds. The length is the one of [`entry_len`].
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Vec<GlobMatcher>,
+    exclude: Vec<GlobMatcher>,
+    regexes: Vec<Regex>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl Filter {
+    /// Parses a filter expression made of whitespace separated terms:
+    ///
+    /// - `**/*.png` includes the paths matching the glob
+    /// - `!**/*.png` excludes the paths matching the glob
+    /// - `re:^/Lotus/Sounds/` includes the paths matching the regex
+    /// - `>10KB` and `<2MB` keep the files strictly longer or shorter than the
+    ///   length
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a glob, regex or size is invalid.
+    pub fn parse(expression: &str) -> Result<Self> {
+        let mut filter = Self::default();
+
+        for term in expression.split_whitespace() {
+            if let Some(pattern) = term.strip_prefix('!') {
+                filter.exclude(pattern)?;
+            } else if let Some(pattern) = term.strip_prefix("re:") {
+                filter.regex(pattern)?;
+            } else if let Some(size) = term.strip_prefix('>') {
+                filter.min_size = Some(parse_size(size)?.saturating_add(1));
+            } else if let Some(size) = term.strip_prefix('<') {
+                let max_size = parse_size(size)?
+                    .checked_sub(1)
+                    .ok_or_else(|| Error::InvalidFilter(format!("{}: no file is shorter", term)))?;
+                filter.max_size = Some(max_size);
+            } else {
+                filter.include(term)?;
+            }
+        }
+
+        Ok(filter)
+    }
+
+    /// Includes the paths matching a glob.
+    pub fn include(&mut self, pattern: &str) -> Result<()> {
+        self.include.push(glob_matcher(pattern)?);
+        Ok(())
+    }
+
+    /// Excludes the paths matching a glob.
+    pub fn exclude(&mut self, pattern: &str) -> Result<()> {
+        self.exclude.push(glob_matcher(pattern)?);
+        Ok(())
+    }
+
+    /// Includes the paths matching a regex.
+    pub fn regex(&mut self, pattern: &str) -> Result<()> {
+        let regex = Regex::new(pattern)
+            .map_err(|err| Error::InvalidFilter(format!("{}: {}", pattern, err)))?;
+        self.regexes.push(regex);
+        Ok(())
+    }
+
+    /// Excludes the files shorter than the given length.
+    pub fn min_size(&mut self, min_size: u64) {
+        self.min_size = Some(min_size);
+    }
+
+    /// Excludes the files longer than the given length.
+    pub fn max_size(&mut self, max_size: u64) {
+        self.max_size = Some(max_size);
+    }
+
+    /// Returns whether the filter lets every file through.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty()
+            && self.exclude.is_empty()
+            && self.regexes.is_empty()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+    }
+
+    /// Returns whether a file matches the filter.
+    pub fn matches(&self, package: &Package<CachePairReader>, file_node: &Node) -> bool {
+        let file_path = file_node.path();
+
+        let included = (self.include.is_empty() && self.regexes.is_empty())
+            || self.include.iter().any(|glob| glob.is_match(&file_path))
+            || self
+                .regexes
+                .iter()
+                .any(|regex| regex.is_match(&file_path.to_string_lossy()));
+        if !included || self.exclude.iter().any(|glob| glob.is_match(&file_path)) {
+            return false;
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let len = entry_len(package, file_node);
+            if self.min_size.is_some_and(|min_size| len < min_size)
+                || self.max_size.is_some_and(|max_size| len > max_size)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Keeps the files matching the filter.
+    pub fn apply(&self, package: &Package<CachePairReader>, mut files: Vec<Node>) -> Vec<Node> {
+        if !self.is_empty() {
+            files.retain(|file_node| self.matches(package, file_node));
+        }
+        files
+    }
+}
+
+impl fmt::Display for Filter {
+    /// Formats the filter as an expression accepted by [`Filter::parse`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms = self
+            .include
+            .iter()
+            .map(|glob| glob.glob().glob().to_string())
+            .chain(self.regexes.iter().map(|regex| format!("re:{}", regex)))
+            .chain(self.exclude.iter().map(|glob| format!("!{}", glob.glob())))
+            // The bounds are inclusive, the operators strict
+            .chain(
+                self.min_size
+                    .filter(|&min_size| min_size > 0)
+                    .map(|min_size| format!(">{}", min_size - 1)),
+            )
+            .chain(
+                self.max_size
+                    .map(|max_size| format!("<{}", max_size.saturating_add(1))),
+            );
+
+        for (index, term) in terms.enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", term)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a length such as `512`, `10KB` or `1.5MB`, using the same decimal
+/// units as the rest of the application.
+///
+/// # Errors
+///
+/// Returns an error if the length is not a number followed by an optional
+/// `B`, `KB`, `MB` or `GB` unit.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let invalid = || Error::InvalidFilter(format!("invalid size {}", size));
+
+    let upper = size.trim().to_ascii_uppercase();
+    let split = upper
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(upper.len());
+    let (number, unit) = upper.split_at(split);
+
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit.trim() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1e3,
+        "M" | "MB" => 1e6,
+        "G" | "GB" => 1e9,
+        _ => return Err(invalid()),
+    };
+
+    Ok((number * multiplier) as u64)
+}
+
+#[inline]
+fn glob_matcher(pattern: &str) -> Result<GlobMatcher> {
+    Glob::new(pattern)
+        .map(|glob| glob.compile_matcher())
+        .map_err(|err| Error::InvalidFilter(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("10KB").unwrap(), 10_000);
+        assert_eq!(parse_size("1.5mb").unwrap(), 1_500_000);
+        assert!(parse_size("10 parsecs").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let filter = Filter::parse("**/*.wav !**/Music/** re:Sounds >1KB <2MB").unwrap();
+        assert_eq!(filter.include.len(), 1);
+        assert_eq!(filter.exclude.len(), 1);
+        assert_eq!(filter.regexes.len(), 1);
+        assert_eq!(filter.min_size, Some(1_001));
+        assert_eq!(filter.max_size, Some(1_999_999));
+
+        assert_eq!(
+            filter.to_string(),
+            "**/*.wav re:Sounds !**/Music/** >1000 <2000000"
+        );
+
+        assert!(Filter::parse("").unwrap().is_empty());
+        assert!(Filter::parse("re:(").is_err());
+        assert!(Filter::parse("<0").is_err());
+    }
+
+    #[test]
+    fn globs() {
+        let matcher = glob_matcher("**/*.png").unwrap();
+        assert!(matcher.is_match("/Lotus/Interface/Icon.png"));
+        assert!(!matcher.is_match("/Lotus/Sounds/Theme.wav"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::cell::OnceCell;
+use std::fmt;
+use std::sync::Arc;
+
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{FileNode, Node};
+
+use crate::error::ExtractError;
+use crate::extract::{decompress_header, decompress_prefix, ExtractOptions};
+
+pub use crate::audio::AudioHandler;
+pub use crate::text::TextHandler;
+pub use crate::texture::TextureHandler;
+
+/// Decoder of the files of a format.
+pub trait FormatHandler: Send + Sync {
+    /// Returns the name of the format, shown to users.
+    fn name(&self) -> &str;
+
+    /// Returns whether the file is in the format of the handler.
+    fn detect(&self, file: &FileContext) -> Result<bool, ExtractError>;
+
+    /// Returns the name of the decoded file, without decoding it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be decoded with the given options.
+    fn output_name(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<String, ExtractError>;
+
+    /// Decodes the file.
+    fn decode(&self, file: &FileContext, options: &ExtractOptions)
+        -> Result<Vec<u8>, ExtractError>;
+
+    /// Decodes the file along with the extra files written next to it, given
+    /// with their names. There are no extra files by default.
+    fn decode_with_extras(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<(Vec<u8>, ExtraFiles), ExtractError> {
+        Ok((self.decode(file, options)?, Vec::new()))
+    }
+}
+
+/// Extra files written next to a decoded file, with their names.
+pub type ExtraFiles = Vec<(String, Vec<u8>)>;
+
+/// File given to the format handlers.
+///
+/// The node is the node of the file in the H cache, the F and B cache entries
+/// sharing its path are found by the handlers if needed. The H cache payload
+/// is decompressed once, on first use, and shared by the detection, naming
+/// and decoding of the file.
+pub struct FileContext<'a> {
+    package: &'a Package<CachePairReader>,
+    node: Node,
+    header_data: OnceCell<Vec<u8>>,
+    header_prefix: OnceCell<Vec<u8>>,
+}
+
+impl<'a> FileContext<'a> {
+    /// Creates the context of a file from its node in the H cache.
+    pub fn new(package: &'a Package<CachePairReader>, node: Node) -> Self {
+        Self {
+            package,
+            node,
+            header_data: OnceCell::new(),
+            header_prefix: OnceCell::new(),
+        }
+    }
+
+    /// Returns the package of the file.
+    pub fn package(&self) -> &'a Package<CachePairReader> {
+        self.package
+    }
+
+    /// Returns the node of the file in the H cache.
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Returns the decompressed H cache payload of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload cannot be decompressed.
+    pub fn header_data(&self) -> Result<&[u8], ExtractError> {
+        if let Some(data) = self.header_data.get() {
+            return Ok(data);
+        }
+        let data = decompress_header(self.package, &self.node)?;
+        Ok(self.header_data.get_or_init(|| data))
+    }
+
+    /// Returns the first `len` bytes of the decompressed H cache payload of
+    /// the file, or the whole payload if it is shorter.
+    ///
+    /// Only the start of the payload is decompressed when the cache allows
+    /// it, unless the whole payload was already decompressed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload cannot be decompressed.
+    pub fn header_prefix(&self, len: usize) -> Result<&[u8], ExtractError> {
+        if let Some(data) = self.header_data.get() {
+            return Ok(data.get(..len).unwrap_or(data));
+        }
+        match self.header_prefix.get() {
+            Some(data) if data.len() >= len => return Ok(&data[..len]),
+            // Decompress the whole payload rather than a longer prefix
+            Some(_) => {
+                let data = self.header_data()?;
+                return Ok(data.get(..len).unwrap_or(data));
+            }
+            None => {}
+        }
+
+        let data = decompress_prefix(self.package, PackageType::H, &self.node, len)?;
+        let data = if data.len() >= self.node.len().max(0) as usize {
+            self.header_data.get_or_init(|| data)
+        } else {
+            self.header_prefix.get_or_init(|| data)
+        };
+        Ok(data.get(..len).unwrap_or(data))
+    }
+}
+
+/// Handler writing the decompressed H cache payload of a file as is, used for
+/// the files no other handler detects.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RawHandler;
+
+impl FormatHandler for RawHandler {
+    fn name(&self) -> &str {
+        "Raw"
+    }
+
+    fn detect(&self, _file: &FileContext) -> Result<bool, ExtractError> {
+        Ok(true)
+    }
+
+    fn output_name(
+        &self,
+        file: &FileContext,
+        _options: &ExtractOptions,
+    ) -> Result<String, ExtractError> {
+        Ok(file.node().name())
+    }
+
+    fn decode(
+        &self,
+        file: &FileContext,
+        _options: &ExtractOptions,
+    ) -> Result<Vec<u8>, ExtractError> {
+        file.header_data().map(<[u8]>::to_vec)
+    }
+}
+
+/// Ordered list of the format handlers used by an extraction.
+///
+/// Handlers are tried in the order they were registered, the [`RawHandler`]
+/// is used for the files none of them detects.
+#[derive(Clone)]
+pub struct Registry {
+    handlers: Vec<Arc<dyn FormatHandler>>,
+}
+
+impl Registry {
+    /// Creates a registry without any handler, writing every file raw.
+    pub fn empty() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Adds a handler, tried after the ones already registered.
+    pub fn register<H: FormatHandler + 'static>(&mut self, handler: H) {
+        self.handlers.push(Arc::new(handler));
+    }
+
+    /// Returns the registered handlers, in the order they are tried.
+    pub fn handlers(&self) -> impl Iterator<Item = &dyn FormatHandler> {
+        self.handlers.iter().map(|handler| handler.as_ref())
+    }
+
+    /// Returns the first handler detecting the file, or the raw handler.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the detection of a handler failed.
+    pub fn find(&self, file: &FileContext) -> Result<&dyn FormatHandler, ExtractError> {
+        for handler in self.handlers() {
+            if handler.detect(file)? {
+                return Ok(handler);
+            }
+        }
+        Ok(&RawHandler)
+    }
+}
+
+impl Default for Registry {
+    /// Creates a registry with the texture, audio and text handlers.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(TextureHandler);
+        registry.register(AudioHandler);
+        registry.register(TextHandler);
+        registry
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.handlers().map(FormatHandler::name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_handlers() {
+        let registry = Registry::default();
+        let names: Vec<&str> = registry.handlers().map(FormatHandler::name).collect();
+        assert_eq!(names, vec!["Texture", "Audio", "Text"]);
+
+        assert_eq!(format!("{:?}", Registry::empty()), "[]");
+    }
+}
@@ -0,0 +1,92 @@
+/// Common header of the H cache entries: a hash, the paths of the merged
+/// source files, the import arguments and the file type, followed by data
+/// specific to the file type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub hash: [u8; 16],
+    pub file_paths: Vec<String>,
+    pub arguments: String,
+    pub file_type: u32,
+    /// Offset of the data following the file type
+    pub offset: usize,
+}
+
+impl Header {
+    /// Parses the common header from the decompressed H cache data.
+    ///
+    /// Returns `None` if the data is too short to contain a header.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let hash: [u8; 16] = data.get(0..16)?.try_into().ok()?;
+        let merged_file_count = read_u32(data, 16)?;
+
+        let mut offset = 20;
+        let mut file_paths = Vec::new();
+        for _ in 0..merged_file_count {
+            let path_length = read_u32(data, offset)? as usize;
+            let path = data.get(offset + 4..offset + 4 + path_length)?;
+            file_paths.push(String::from_utf8_lossy(path).into_owned());
+            offset += 4 + path_length;
+        }
+
+        let arguments_length = read_u32(data, offset)? as usize;
+        offset += 4;
+
+        let arguments = data.get(offset..offset + arguments_length)?;
+        let arguments = String::from_utf8_lossy(arguments).into_owned();
+        offset += arguments_length;
+
+        // If the arguments length is > 0, then we need to skip the null byte
+        if arguments_length > 0 {
+            offset += 1;
+        }
+
+        let file_type = read_u32(data, offset)?;
+        offset += 4;
+
+        Some(Self {
+            hash,
+            file_paths,
+            arguments,
+            file_type,
+            offset,
+        })
+    }
+}
+
+/// Reads a little endian `u32` at the given offset.
+#[inline]
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let mut data = vec![0xAA; 16];
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&5u32.to_le_bytes());
+        data.extend_from_slice(b"a.png");
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"abc\0");
+        data.extend_from_slice(&0xA3u32.to_le_bytes());
+        data.push(0x42);
+
+        let header = Header::parse(&data).unwrap();
+
+        assert_eq!(header.hash, [0xAA; 16]);
+        assert_eq!(header.file_paths, vec!["a.png".to_string()]);
+        assert_eq!(header.arguments, "abc");
+        assert_eq!(header.file_type, 0xA3);
+        assert_eq!(data[header.offset], 0x42);
+    }
+
+    #[test]
+    fn parse_truncated() {
+        assert_eq!(Header::parse(&[0; 19]), None);
+        assert_eq!(Header::parse(&[0; 24]), None);
+    }
+}
@@ -0,0 +1,52 @@
+/*!
+
+# wfcache-api
+
+Library to read and extract data from Warframe's cache files, as used by the
+`wfcache-api` binary.
+
+```no_run
+use wfcache_api::extract::{self, ExtractOptions};
+use wfcache_api::{sink, Cache};
+
+let mut cache = Cache::open("Cache.Windows")?;
+let package = cache.package("Misc")?;
+
+// The output may be a directory or an archive, which is opened once so that
+// it holds every file
+let output = "Extracted.zip".as_ref();
+let sink = sink::open(output)?;
+
+let node = wfcache_api::get_node(&package, "/Lotus/Music")?;
+for file_node in extract::collect_files(&node, true) {
+    let options = ExtractOptions::default();
+    extract::extract_file_to(&package, &file_node, output, sink.as_ref(), &options)?;
+}
+sink.finish()?;
+# Ok::<(), wfcache_api::Error>(())
+```
+
+*/
+
+mod audio;
+mod cache;
+mod error;
+pub mod extract;
+mod filter;
+pub mod format;
+mod header;
+pub mod manifest;
+mod navigator;
+pub mod sink;
+mod text;
+mod texture;
+
+pub use cache::{get_node, list_directory, load_package, Cache};
+pub use error::{Error, ExtractError, Result};
+pub use filter::{parse_size, Filter};
+pub use header::Header;
+pub use navigator::Navigator;
+
+pub use lotus_lib::cache_pair::CachePairReader;
+pub use lotus_lib::package::{Package, PackageType};
+pub use lotus_lib::toc::{DirectoryNode, FileNode, Node, NodeKind};
@@ -1,8 +1,9 @@
 mod action;
 mod app;
 mod args;
+mod commands;
 mod errors;
-mod extract;
+mod shell;
 mod tui;
 mod widgets;
 
@@ -12,8 +13,28 @@ use color_eyre::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    errors::install_hooks()?;
     let args = args::Args::parse();
+    let extract_options = args.extract_options()?;
+
+    // Run the commands or the shell without entering the TUI
+    if args.shell || args.command.is_some() || !args.commands.is_empty() {
+        errors::install_headless_hooks()?;
+        let package = wfcache_api::load_package(args.directory, &args.package)?;
+        let mut session = commands::Session::new(package, args.output, extract_options)?;
+
+        for line in &args.commands {
+            session.execute_line(line)?;
+        }
+        if let Some(command) = args.command {
+            session.execute(command)?;
+        }
+        if args.shell {
+            shell::run(session)?;
+        }
+        return Ok(());
+    }
+
+    errors::install_hooks()?;
 
     // Initialize the ratatui terminal
     let mut tui = tui::Tui::new()
@@ -23,7 +44,7 @@ async fn main() -> Result<()> {
     tui.enter().wrap_err("Failed to enter TUI")?;
 
     // Run the ratatui app
-    app::App::try_init(args.directory, args.package, args.output)?
+    app::App::try_init(args.directory, args.package, args.output, extract_options)?
         .run(&mut tui)
         .await?;
 
@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{FileNode, Node};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+
+/// File format of a manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ManifestFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ManifestFormat {
+    /// Returns the name of the manifest file written in the output directory.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "manifest.json",
+            Self::Csv => "manifest.csv",
+        }
+    }
+}
+
+/// Record of the files produced by an extraction and of the cache entries
+/// they were produced from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Files of an extraction compared to a previous manifest.
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    /// Files that are new or whose cache entries changed
+    pub files: Vec<Node>,
+    /// Entries of the files whose cache entries did not change
+    pub unchanged: Vec<ManifestEntry>,
+    /// Entries of the files that are no longer in the package
+    pub removed: Vec<ManifestEntry>,
+}
+
+/// Cache entry of a file in one of the H, F or B caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub cache_offset: i64,
+    pub timestamp: i64,
+    pub comp_len: i32,
+    pub len: i32,
+}
+
+impl CacheEntry {
+    fn new(node: &Node) -> Self {
+        Self {
+            cache_offset: node.cache_offset(),
+            timestamp: node.timestamp(),
+            comp_len: node.comp_len(),
+            len: node.len(),
+        }
+    }
+}
+
+/// Manifest entry of an extracted file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// TOC path of the file
+    pub path: String,
+    /// Package the file belongs to
+    pub package: String,
+    /// Entries of the caches containing the file
+    pub h: Option<CacheEntry>,
+    pub f: Option<CacheEntry>,
+    pub b: Option<CacheEntry>,
+    /// Path of the output file, relative to the output directory
+    pub output: String,
+    /// SHA-256 of the output file
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    /// Creates the entry of a file extracted to the output path, reading the
+    /// cache entries sharing its path.
+    pub fn new(
+        package: &Package<CachePairReader>,
+        file_node: &Node,
+        output_dir: &Path,
+        output_path: &Path,
+        sha256: String,
+    ) -> Self {
+        let file_path = file_node.path();
+        let cache_entry = |package_type| {
+            package
+                .borrow(package_type)
+                .and_then(|cache| cache.get_file_node(&file_path))
+                .map(|node| CacheEntry::new(&node))
+        };
+
+        let output = output_path
+            .strip_prefix(output_dir)
+            .unwrap_or(output_path)
+            .to_string_lossy()
+            .into_owned();
+
+        Self {
+            path: file_path.to_string_lossy().into_owned(),
+            package: package.name().clone(),
+            h: cache_entry(PackageType::H),
+            f: cache_entry(PackageType::F),
+            b: cache_entry(PackageType::B),
+            output,
+            sha256,
+        }
+    }
+
+    /// Returns the caches containing the file (e.g. `HF`).
+    pub fn caches(&self) -> String {
+        [('H', &self.h), ('F', &self.f), ('B', &self.b)]
+            .into_iter()
+            .filter(|(_, entry)| entry.is_some())
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// Returns the uncompressed length summed over the caches.
+    pub fn total_len(&self) -> i64 {
+        self.cache_entries().map(|entry| entry.len as i64).sum()
+    }
+
+    /// Returns the compressed length summed over the caches.
+    pub fn total_comp_len(&self) -> i64 {
+        self.cache_entries()
+            .map(|entry| entry.comp_len as i64)
+            .sum()
+    }
+
+    /// Returns the most recent timestamp of the caches.
+    pub fn timestamp(&self) -> i64 {
+        self.cache_entries()
+            .map(|entry| entry.timestamp)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the cache entries of a file are the ones of the entry.
+    fn same_cache_entries(&self, package: &Package<CachePairReader>, file_node: &Node) -> bool {
+        let file_path = file_node.path();
+        let cache_entry = |package_type| {
+            package
+                .borrow(package_type)
+                .and_then(|cache| cache.get_file_node(&file_path))
+                .map(|node| CacheEntry::new(&node))
+        };
+
+        self.h == cache_entry(PackageType::H)
+            && self.f == cache_entry(PackageType::F)
+            && self.b == cache_entry(PackageType::B)
+    }
+
+    fn cache_entries(&self) -> impl Iterator<Item = &CacheEntry> {
+        [&self.h, &self.f, &self.b].into_iter().flatten()
+    }
+}
+
+impl Manifest {
+    /// Reads a JSON manifest.
+    ///
+    /// CSV manifests are rejected, as they only record the lengths and
+    /// timestamps summed over the caches instead of each cache entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is a CSV manifest, cannot be read or is not
+    /// a valid manifest.
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        if is_csv {
+            return Err(Error::InvalidManifest(
+                path.to_path_buf(),
+                String::from("CSV manifests cannot be compared, use a JSON manifest"),
+            ));
+        }
+
+        let io_error = |source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let file = File::open(path).map_err(io_error)?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|err| {
+            if err.is_io() {
+                io_error(err.into())
+            } else {
+                Error::InvalidManifest(path.to_path_buf(), err.to_string())
+            }
+        })
+    }
+
+    /// Compares files of a package to the manifest.
+    ///
+    /// A file is unchanged if the offset, timestamp and lengths of its H, F and
+    /// B cache entries are the same as in the manifest. The entries of the
+    /// package whose file no longer exists are reported as removed.
+    pub fn changes(&self, package: &Package<CachePairReader>, files: &[Node]) -> Changes {
+        let entries: HashMap<&str, &ManifestEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.package == *package.name())
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+
+        let mut changes = Changes::default();
+
+        for file_node in files {
+            let file_path = file_node.path();
+            let previous = file_path
+                .to_str()
+                .and_then(|file_path| entries.get(file_path));
+
+            match previous {
+                Some(&entry) if entry.same_cache_entries(package, file_node) => {
+                    changes.unchanged.push(entry.clone())
+                }
+                _ => changes.files.push(file_node.clone()),
+            }
+        }
+
+        let h_cache = package.borrow(PackageType::H);
+        changes.removed = entries
+            .into_values()
+            .filter(|entry| {
+                h_cache
+                    .and_then(|cache| cache.get_file_node(&entry.path))
+                    .is_none()
+            })
+            .cloned()
+            .collect();
+        changes.removed.sort_by(|a, b| a.path.cmp(&b.path));
+
+        changes
+    }
+
+    /// Adds the entries of a previous manifest that are still up to date: the
+    /// unchanged files along with the files outside of the compared ones.
+    pub fn carry_over(&mut self, previous: &Manifest, package_name: &str, changes: &Changes) {
+        let excluded: HashSet<String> = changes
+            .files
+            .iter()
+            .map(|file_node| file_node.path().to_string_lossy().into_owned())
+            .chain(changes.removed.iter().map(|entry| entry.path.clone()))
+            .collect();
+
+        self.entries.extend(
+            previous
+                .entries
+                .iter()
+                .filter(|entry| entry.package != package_name || !excluded.contains(&entry.path))
+                .cloned(),
+        );
+        self.entries
+            .sort_by(|a, b| (&a.package, &a.path).cmp(&(&b.package, &b.path)));
+    }
+
+    /// Adds the entries of a previous manifest of the same output directory
+    /// whose files are not in this manifest, so that extracting in several
+    /// runs keeps the record of the previous ones.
+    pub fn merge(&mut self, previous: &Manifest) {
+        let paths: HashSet<(&str, &str)> = self
+            .entries
+            .iter()
+            .map(|entry| (entry.package.as_str(), entry.path.as_str()))
+            .collect();
+
+        let previous_entries: Vec<ManifestEntry> = previous
+            .entries
+            .iter()
+            .filter(|entry| !paths.contains(&(entry.package.as_str(), entry.path.as_str())))
+            .cloned()
+            .collect();
+
+        self.entries.extend(previous_entries);
+        self.entries
+            .sort_by(|a, b| (&a.package, &a.path).cmp(&(&b.package, &b.path)));
+    }
+
+    /// Serializes the manifest in the given format.
+    pub fn to_bytes(&self, format: ManifestFormat) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        match format {
+            ManifestFormat::Json => serde_json::to_writer_pretty(&mut data, self)?,
+            ManifestFormat::Csv => self.write_csv(&mut data)?,
+        }
+        Ok(data)
+    }
+
+    fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "path,package,caches,len,comp_len,timestamp,output,sha256"
+        )?;
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                csv_field(&entry.path),
+                csv_field(&entry.package),
+                entry.caches(),
+                entry.total_len(),
+                entry.total_comp_len(),
+                entry.timestamp(),
+                csv_field(&entry.output),
+                entry.sha256
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the SHA-256 of the data as a hexadecimal string.
+pub fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash() {
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    fn entry(package: &str, path: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            package: package.to_string(),
+            h: None,
+            f: None,
+            b: None,
+            output: path.trim_start_matches('/').to_string(),
+            sha256: String::new(),
+        }
+    }
+
+    #[test]
+    fn carry_over() {
+        let previous = Manifest {
+            entries: vec![
+                entry("Misc", "/Lotus/A.png"),
+                entry("Misc", "/Lotus/B.png"),
+                entry("Font", "/Lotus/B.png"),
+            ],
+        };
+        let changes = Changes {
+            removed: vec![entry("Misc", "/Lotus/B.png")],
+            ..Default::default()
+        };
+
+        let mut manifest = Manifest::default();
+        manifest.carry_over(&previous, "Misc", &changes);
+
+        assert_eq!(
+            manifest.entries,
+            vec![entry("Font", "/Lotus/B.png"), entry("Misc", "/Lotus/A.png")]
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let previous = Manifest {
+            entries: vec![entry("Misc", "/Lotus/A.png"), entry("Misc", "/Lotus/B.png")],
+        };
+
+        let mut b = entry("Misc", "/Lotus/B.png");
+        b.sha256 = String::from("new");
+        let mut manifest = Manifest {
+            entries: vec![b.clone()],
+        };
+        manifest.merge(&previous);
+
+        assert_eq!(manifest.entries, vec![entry("Misc", "/Lotus/A.png"), b]);
+    }
+
+    #[test]
+    fn read_csv() {
+        let result = Manifest::read(Path::new("Extracted/manifest.csv"));
+        assert!(matches!(result, Err(Error::InvalidManifest(..))));
+    }
+
+    #[test]
+    fn read_invalid_json() {
+        let path = std::env::temp_dir().join("wfcache-api-invalid-manifest.json");
+        std::fs::write(&path, b"{\"entries\": [").unwrap();
+        let result = Manifest::read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::InvalidManifest(..))));
+    }
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(
+            csv_field("/Lotus/Music/Theme.wav"),
+            "/Lotus/Music/Theme.wav"
+        );
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+}
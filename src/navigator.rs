@@ -0,0 +1,112 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use derivative::Derivative;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::Node;
+
+use crate::cache;
+use crate::error::{Error, Result};
+
+/// Navigation state over the H cache of a package: the current directory and
+/// its listing.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Navigator {
+    #[derivative(Debug = "ignore")]
+    package: Arc<Package<CachePairReader>>,
+    cwd: PathBuf,
+    directory: Node,
+    directories: Vec<Node>,
+    files: Vec<Node>,
+}
+
+impl Navigator {
+    /// Creates a navigator at the root directory of the package.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CacheNotFound`] if the package has no H cache.
+    pub fn new(package: Arc<Package<CachePairReader>>) -> Result<Self> {
+        // The root directory exists in every H cache
+        let (directory, directories, files) =
+            cache::list_directory(&package, "/").ok_or(Error::CacheNotFound(PackageType::H))?;
+
+        Ok(Self {
+            package,
+            cwd: PathBuf::from("/"),
+            directory,
+            directories,
+            files,
+        })
+    }
+
+    #[inline]
+    pub fn package(&self) -> &Arc<Package<CachePairReader>> {
+        &self.package
+    }
+
+    #[inline]
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// Returns the node of the current directory.
+    #[inline]
+    pub fn directory(&self) -> &Node {
+        &self.directory
+    }
+
+    /// Returns the subdirectories of the current directory, sorted by name.
+    #[inline]
+    pub fn directories(&self) -> &[Node] {
+        &self.directories
+    }
+
+    /// Returns the files of the current directory, sorted by name.
+    #[inline]
+    pub fn files(&self) -> &[Node] {
+        &self.files
+    }
+
+    /// Changes the current directory, resolving the path relative to it.
+    ///
+    /// Returns `false` if the path is not a directory of the H cache.
+    pub fn change_directory<P>(&mut self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.resolve(path);
+
+        match cache::list_directory(&self.package, &path) {
+            Some((directory, directories, files)) => {
+                self.cwd = path;
+                self.directory = directory;
+                self.directories = directories;
+                self.files = files;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resolves a path relative to the current directory into an absolute
+    /// path without `.` and `..` components.
+    pub fn resolve<P>(&self, path: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let mut resolved = PathBuf::from("/");
+        for component in self.cwd.join(path).components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::Normal(name) => resolved.push(name),
+                Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+            }
+        }
+        resolved
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::CommandFactory;
+use color_eyre::Result;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::Package;
+use lotus_lib::toc::NodeKind;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::args::CommandLine;
+use crate::commands::Session;
+
+const HISTORY_FILE: &str = ".wfcache-api_history";
+
+/// Runs an interactive shell reading commands line by line until the user
+/// exits.
+pub fn run(mut session: Session) -> Result<()> {
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper::new(session.package().clone())));
+
+    let history_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(history_path) = &history_path {
+        let _ = editor.load_history(history_path);
+    }
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.cwd = session.cwd().to_path_buf();
+        }
+
+        let prompt = format!("{}:{}> ", session.package().name(), session.cwd().display());
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        if let Err(err) = session.execute_line(line) {
+            eprintln!("{}", err);
+        }
+    }
+
+    if let Some(history_path) = &history_path {
+        let _ = editor.save_history(history_path);
+    }
+
+    Ok(())
+}
+
+/// Completes command names and TOC paths of the H cache
+struct ShellHelper {
+    package: Arc<Package<CachePairReader>>,
+    cwd: PathBuf,
+    commands: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new(package: Arc<Package<CachePairReader>>) -> Self {
+        let mut commands: Vec<String> = CommandLine::command()
+            .get_subcommands()
+            .map(|command| command.get_name().to_string())
+            .collect();
+        commands.extend(["exit".to_string(), "quit".to_string()]);
+        commands.sort();
+
+        Self {
+            package,
+            cwd: PathBuf::from("/"),
+            commands,
+        }
+    }
+
+    fn complete_command(&self, word: &str) -> Vec<Pair> {
+        self.commands
+            .iter()
+            .filter(|command| command.starts_with(word))
+            .map(|command| Pair {
+                display: command.clone(),
+                replacement: format!("{} ", command),
+            })
+            .collect()
+    }
+
+    fn complete_path(&self, word: &str) -> Vec<Pair> {
+        let (directory, prefix) = match word.rfind('/') {
+            Some(index) => word.split_at(index + 1),
+            None => ("", word),
+        };
+
+        let Some((_, directories, files)) =
+            wfcache_api::list_directory(&self.package, self.cwd.join(directory))
+        else {
+            return Vec::new();
+        };
+
+        directories
+            .into_iter()
+            .chain(files)
+            .map(|node| (node.name(), node.kind()))
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, kind)| {
+                let (display, replacement) = match kind {
+                    NodeKind::Directory => {
+                        (format!("{}/", name), format!("{}{}/", directory, name))
+                    }
+                    NodeKind::File => (name.clone(), format!("{}{} ", directory, name)),
+                };
+                Pair {
+                    display,
+                    replacement,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let word = &line[start..];
+
+        let candidates = if line[..start].trim().is_empty() {
+            self.complete_command(word)
+        } else {
+            self.complete_path(word)
+        };
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
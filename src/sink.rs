@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::error::ExtractError;
+
+/// Destination of the files written by an extraction.
+///
+/// Paths are relative to the root of the sink, which is the output directory
+/// of the extraction. Sinks are shared between the extraction threads.
+pub trait Sink: Send + Sync {
+    /// Returns the metadata of a file written before the extraction, used by
+    /// the overwrite policies.
+    fn metadata(&self, _path: &Path) -> Option<Metadata> {
+        None
+    }
+
+    /// Reads back a file written before the extraction.
+    fn read(&self, _path: &Path) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Writes a file, creating its parent directories if needed.
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), ExtractError>;
+
+    /// Writes a file that sums up the extractions into the sink, such as the
+    /// manifest, replacing the one written at the same path before. Sinks that
+    /// cannot replace files write it once finished.
+    fn write_summary(&self, path: &Path, data: &[u8]) -> Result<(), ExtractError> {
+        self.write(path, data)
+    }
+
+    /// Finishes writing, no file can be written afterwards.
+    fn finish(&self) -> Result<(), ExtractError> {
+        Ok(())
+    }
+}
+
+/// Format of an archive the files of an extraction are written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    Tar,
+    /// Tar archive compressed with Zstandard
+    TarZst,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Returns the format of an archive from the extension of its path
+    /// (`.tar`, `.tar.zst`, `.tzst` or `.zip`), or `None` for a directory.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if file_name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if file_name.ends_with(".tar.zst") || file_name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Opens the sink of an output path: an archive if its extension is the one
+/// of an [`ArchiveFormat`], a directory otherwise.
+///
+/// # Errors
+///
+/// Returns an error if the archive cannot be created.
+pub fn open(output: &Path) -> Result<Box<dyn Sink>, ExtractError> {
+    Ok(match ArchiveFormat::from_path(output) {
+        Some(format) => Box::new(Archive::create(output, format)?),
+        None => Box::new(Directory::new(output)),
+    })
+}
+
+/// Sink writing the files in a directory of the filesystem.
+#[derive(Debug, Clone)]
+pub struct Directory {
+    root: PathBuf,
+}
+
+impl Directory {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Sink for Directory {
+    fn metadata(&self, path: &Path) -> Option<Metadata> {
+        std::fs::metadata(self.root.join(path)).ok()
+    }
+
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        std::fs::read(self.root.join(path)).ok()
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), ExtractError> {
+        let path = self.root.join(path);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|source| ExtractError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+
+        std::fs::write(&path, data).map_err(|source| ExtractError::Io { path, source })
+    }
+}
+
+/// Sink writing the files in a new archive, replacing any existing file.
+///
+/// The archive may be extracted into several times: a file is only written
+/// the first time, as the package does not change in the meantime, and the
+/// summaries are kept in memory until the archive is finished. The archive
+/// is only valid once [`Sink::finish`] was called.
+pub struct Archive {
+    path: PathBuf,
+    writer: Mutex<Option<ArchiveWriter>>,
+    /// Paths of the entries written so far
+    entries: Mutex<HashSet<String>>,
+    /// Summaries written when the archive is finished
+    summaries: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+enum ArchiveWriter {
+    Tar(tar::Builder<BufWriter<File>>),
+    TarZst(tar::Builder<zstd::Encoder<'static, BufWriter<File>>>),
+    Zip(Box<ZipWriter<BufWriter<File>>>),
+}
+
+impl Archive {
+    /// Creates an archive of the given format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive file cannot be created.
+    pub fn create(path: &Path, format: ArchiveFormat) -> Result<Self, ExtractError> {
+        let io_error = |source| ExtractError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let file = BufWriter::new(File::create(path).map_err(io_error)?);
+
+        let writer = match format {
+            ArchiveFormat::Tar => ArchiveWriter::Tar(tar::Builder::new(file)),
+            ArchiveFormat::TarZst => {
+                let encoder = zstd::Encoder::new(file, 0).map_err(io_error)?;
+                ArchiveWriter::TarZst(tar::Builder::new(encoder))
+            }
+            ArchiveFormat::Zip => {
+                ArchiveWriter::Zip(Box::new(ZipWriter::new(file).set_auto_large_file()))
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: Mutex::new(Some(writer)),
+            entries: Mutex::new(HashSet::new()),
+            summaries: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn io_error(&self, source: io::Error) -> ExtractError {
+        ExtractError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+impl Sink for Archive {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        self.summaries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&archive_path(path))
+            .cloned()
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), ExtractError> {
+        let entry_path = archive_path(path);
+
+        // A panic while writing another file leaves the locks poisoned
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if writer.is_some() && entries.contains(&entry_path) {
+            return Ok(());
+        }
+
+        let result = match writer.as_mut() {
+            Some(ArchiveWriter::Tar(builder)) => append_tar(builder, &entry_path, data),
+            Some(ArchiveWriter::TarZst(builder)) => append_tar(builder, &entry_path, data),
+            Some(ArchiveWriter::Zip(zip)) => append_zip(zip, &entry_path, data),
+            None => Err(io::Error::other("archive already finished")),
+        };
+        if result.is_ok() {
+            entries.insert(entry_path);
+        }
+
+        result.map_err(|source| self.io_error(source))
+    }
+
+    fn write_summary(&self, path: &Path, data: &[u8]) -> Result<(), ExtractError> {
+        self.summaries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(archive_path(path), data.to_vec());
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), ExtractError> {
+        let summaries = std::mem::take(
+            &mut *self
+                .summaries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        for (entry_path, data) in summaries {
+            self.write(Path::new(&entry_path), &data)?;
+        }
+
+        let Some(writer) = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return Ok(());
+        };
+
+        let result = match writer {
+            ArchiveWriter::Tar(builder) => builder.into_inner().and_then(|mut file| file.flush()),
+            ArchiveWriter::TarZst(builder) => builder
+                .into_inner()
+                .and_then(zstd::Encoder::finish)
+                .and_then(|mut file| file.flush()),
+            ArchiveWriter::Zip(zip) => (*zip)
+                .finish()
+                .map_err(io::Error::from)
+                .and_then(|mut file| file.flush()),
+        };
+
+        result.map_err(|source| self.io_error(source))
+    }
+}
+
+/// Returns the path of an entry in an archive, with `/` separators.
+fn archive_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            std::path::Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn append_tar<W: Write>(builder: &mut tar::Builder<W>, path: &str, data: &[u8]) -> io::Result<()> {
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    builder.append_data(&mut header, path, data)
+}
+
+fn append_zip<W: Write + io::Seek>(
+    zip: &mut ZipWriter<W>,
+    path: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(data.len() as u64 >= u32::MAX as u64);
+    zip.start_file(path, options)?;
+    zip.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let format = |path: &str| ArchiveFormat::from_path(Path::new(path));
+        assert_eq!(format("Extracted"), None);
+        assert_eq!(format("Extracted.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(format("out/Extracted.TAR.ZST"), Some(ArchiveFormat::TarZst));
+        assert_eq!(format("Extracted.zip"), Some(ArchiveFormat::Zip));
+    }
+
+    #[test]
+    fn archive_paths() {
+        assert_eq!(
+            archive_path(Path::new("Lotus/Interface/Icon.png")),
+            "Lotus/Interface/Icon.png"
+        );
+        assert_eq!(archive_path(Path::new("/manifest.json")), "manifest.json");
+    }
+
+    #[test]
+    fn archive_entries() {
+        let path = std::env::temp_dir().join("wfcache-api-entries.tar");
+        let archive = Archive::create(&path, ArchiveFormat::Tar).unwrap();
+        archive.write(Path::new("Lotus/A.txt"), b"a").unwrap();
+        archive.write(Path::new("Lotus/A.txt"), b"a").unwrap();
+        archive
+            .write_summary(Path::new("manifest.json"), b"1")
+            .unwrap();
+        archive
+            .write_summary(Path::new("manifest.json"), b"2")
+            .unwrap();
+        assert_eq!(
+            archive.read(Path::new("manifest.json")),
+            Some(b"2".to_vec())
+        );
+        archive.finish().unwrap();
+
+        let mut entries = Vec::new();
+        for entry in tar::Archive::new(File::open(&path).unwrap())
+            .entries()
+            .unwrap()
+        {
+            let mut entry = entry.unwrap();
+            let mut data = String::new();
+            io::Read::read_to_string(&mut entry, &mut data).unwrap();
+            entries.push((entry.path().unwrap().display().to_string(), data));
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            entries,
+            [
+                (String::from("Lotus/A.txt"), String::from("a")),
+                (String::from("manifest.json"), String::from("2"))
+            ]
+        );
+    }
+}
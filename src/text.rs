@@ -0,0 +1,250 @@
+//! Detection and decoding of the files whose payload is text, such as type
+//! definitions, manifests and localisation files.
+
+use lotus_lib::toc::FileNode;
+
+use crate::error::ExtractError;
+use crate::extract::ExtractOptions;
+use crate::format::{FileContext, FormatHandler};
+use crate::header::{read_u32, Header};
+
+/// Length of the text read by [`TextHandler::read`] above which payloads are
+/// not read, as the whole payload is decoded.
+const MAX_TEXT_LEN: i32 = 16_000_000;
+
+/// Number of bytes following the header checked to detect text and JSON.
+const SNIFF_LEN: usize = 4096;
+
+/// Handler of the files whose H payload is text, exported as UTF-8 `.json`
+/// files if they hold JSON and as `.txt` files otherwise.
+///
+/// Only the start of the payload is checked to detect text and name the
+/// output file, the payload is decoded in full once, by [`decode`].
+///
+/// [`decode`]: FormatHandler::decode
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextHandler;
+
+impl FormatHandler for TextHandler {
+    fn name(&self) -> &str {
+        "Text"
+    }
+
+    fn detect(&self, file: &FileContext) -> Result<bool, ExtractError> {
+        Ok(self.sniff(file)?.is_some())
+    }
+
+    fn output_name(
+        &self,
+        file: &FileContext,
+        _options: &ExtractOptions,
+    ) -> Result<String, ExtractError> {
+        let (text, truncated) = self
+            .sniff(file)?
+            .ok_or_else(|| ExtractError::UnknownFormat(String::from("binary data")))?;
+        Ok(file_name(&file.node().name(), is_json(&text, truncated)))
+    }
+
+    fn decode(
+        &self,
+        file: &FileContext,
+        _options: &ExtractOptions,
+    ) -> Result<Vec<u8>, ExtractError> {
+        let payload = file.header_data()?;
+        // The payload is written as is if only its start is text
+        Ok(
+            decode_text(payload, payload.len())
+                .map_or_else(|| payload.to_vec(), String::into_bytes),
+        )
+    }
+}
+
+impl TextHandler {
+    /// Reads the text of a file, decoded to UTF-8 with `\n` line endings.
+    ///
+    /// Returns `None` if the file is binary data or too long to be read. Only
+    /// the start of the payload is decompressed to find out if it is text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the H cache payload cannot be decompressed.
+    pub fn read(&self, file: &FileContext) -> Result<Option<String>, ExtractError> {
+        if file.node().len() > MAX_TEXT_LEN || self.sniff(file)?.is_none() {
+            return Ok(None);
+        }
+        let payload = file.header_data()?;
+        Ok(decode_text(payload, payload.len()))
+    }
+
+    /// Decodes the start of the text of a file, at most [`SNIFF_LEN`] bytes
+    /// following its header.
+    ///
+    /// Returns the text and whether it is truncated, or `None` if the file is
+    /// binary data.
+    fn sniff(&self, file: &FileContext) -> Result<Option<(String, bool)>, ExtractError> {
+        let len = file.node().len().max(0) as usize;
+        let mut prefix = file.header_prefix(SNIFF_LEN)?;
+        if let Some(header) = Header::parse(prefix) {
+            prefix = file.header_prefix(header.offset + SNIFF_LEN)?;
+        }
+        Ok(decode_text(prefix, len).map(|text| (text, prefix.len() < len)))
+    }
+}
+
+/// Returns the name of a text file, appending the `.json` or `.txt` extension
+/// unless the name already has it.
+pub(crate) fn file_name(name: &str, json: bool) -> String {
+    let extension = if json { ".json" } else { ".txt" };
+    if name.to_ascii_lowercase().ends_with(extension) {
+        name.to_string()
+    } else {
+        format!("{}{}", name, extension)
+    }
+}
+
+/// Returns whether a text is a JSON object or array, or the start of one if
+/// the text is truncated.
+pub(crate) fn is_json(text: &str, truncated: bool) -> bool {
+    if !text.trim_start().starts_with(['{', '[']) {
+        return false;
+    }
+    match serde_json::from_str::<serde::de::IgnoredAny>(text) {
+        Ok(_) => true,
+        Err(err) => truncated && err.is_eof(),
+    }
+}
+
+/// Decodes a payload holding text, with or without the common H cache header,
+/// to UTF-8 with `\n` line endings. The payload may be the start of a longer
+/// one of the given length.
+///
+/// Returns `None` if the payload is binary data.
+pub(crate) fn decode_text(payload: &[u8], len: usize) -> Option<String> {
+    // Headers could be mistaken for text, try the data following them first
+    let body = Header::parse(payload).and_then(|header| {
+        let body = payload.get(header.offset..)?;
+        decode_body(body, len.checked_sub(header.offset)?)
+    });
+    body.or_else(|| decode_body(payload, len))
+}
+
+/// Decodes the text of a body of the given length, of which `data` may only be
+/// the start.
+fn decode_body(mut data: &[u8], len: usize) -> Option<String> {
+    let truncated = data.len() < len;
+
+    // The text may be prefixed with its length
+    if let (Some(text_len), Some(rest_len)) = (read_u32(data, 0), len.checked_sub(4)) {
+        if text_len as usize == rest_len || text_len as usize + 1 == rest_len {
+            data = &data[4..];
+        }
+    }
+
+    let text = match data {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => utf8(rest, truncated)?,
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, truncated, u16::from_le_bytes)?,
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, truncated, u16::from_be_bytes)?,
+        _ => utf8(data, truncated)?,
+    };
+
+    // Trailing NUL characters terminate the text
+    let text = text.trim_end_matches('\0');
+
+    let is_text = !text.trim().is_empty()
+        && !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C'));
+    is_text.then(|| text.replace("\r\n", "\n"))
+}
+
+fn utf8(data: &[u8], truncated: bool) -> Option<String> {
+    match std::str::from_utf8(data) {
+        Ok(text) => Some(text.to_string()),
+        // The data may end in the middle of a character
+        Err(err) if truncated && err.error_len().is_none() => {
+            Some(String::from_utf8_lossy(&data[..err.valid_up_to()]).into_owned())
+        }
+        Err(_) => None,
+    }
+}
+
+fn utf16(data: &[u8], truncated: bool, from_bytes: fn([u8; 2]) -> u16) -> Option<String> {
+    if !truncated && !data.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| from_bytes([unit[0], unit[1]]))
+        .collect();
+
+    // The data may end in the middle of a surrogate pair
+    if truncated
+        && units
+            .last()
+            .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+    {
+        units.pop();
+    }
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("Types.lua", false), "Types.lua.txt");
+        assert_eq!(file_name("Manifest.json", true), "Manifest.json");
+        assert_eq!(file_name("Manifest", true), "Manifest.json");
+    }
+
+    #[test]
+    fn json() {
+        assert!(is_json(" {\"a\": [1, 2]}", false));
+        assert!(!is_json("{ not json", false));
+        assert!(!is_json("42", false));
+
+        assert!(is_json("{\"a\": [1,", true));
+        assert!(!is_json("{\"a\": [1,", false));
+        assert!(!is_json("[Section]\nkey=value", true));
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_text(b"a\r\nb\0", 5).unwrap(), "a\nb");
+        assert_eq!(decode_text(b"\xEF\xBB\xBFabc", 6).unwrap(), "abc");
+        assert_eq!(decode_text(b"\xFF\xFEa\0b\0", 6).unwrap(), "ab");
+
+        let mut prefixed = 3u32.to_le_bytes().to_vec();
+        prefixed.extend_from_slice(b"abc");
+        assert_eq!(decode_text(&prefixed, prefixed.len()).unwrap(), "abc");
+
+        assert_eq!(decode_text(b"\x01\x02\x03", 3), None);
+        assert_eq!(decode_text(b"\0\0", 2), None);
+        // Lengths shorter than a length prefix
+        assert_eq!(decode_text(b"abcd", 3).unwrap(), "abcd");
+    }
+
+    #[test]
+    fn decode_with_header() {
+        let mut data = vec![0xAA; 16];
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x10u32.to_le_bytes());
+        data.extend_from_slice(b"key=value");
+
+        assert_eq!(decode_text(&data, data.len()).unwrap(), "key=value");
+    }
+
+    #[test]
+    fn decode_truncated() {
+        assert_eq!(decode_text(&"ab\u{e9}".as_bytes()[..3], 4).unwrap(), "ab");
+        assert_eq!(decode_text(b"\xFF\xFEa\0b", 6).unwrap(), "a");
+        assert_eq!(decode_text(b"\xFF\xFEa\0b", 5), None);
+
+        let mut prefixed = 4u32.to_le_bytes().to_vec();
+        prefixed.extend_from_slice(b"ab");
+        assert_eq!(decode_text(&prefixed, 8).unwrap(), "ab");
+    }
+}
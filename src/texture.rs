@@ -0,0 +1,355 @@
+//! Conversions of the DDS files written by `decompress_texture` to the image
+//! formats requested by [`TextureFormat`].
+
+use std::io::Cursor;
+
+use ddsfile::Dds;
+use image::{ImageFormat, RgbaImage};
+use lotus_lib::package::PackageType;
+use lotus_lib::toc::FileNode;
+use lotus_utils_texture::Texture;
+
+use crate::error::ExtractError;
+use crate::extract::{cache_node, ExtractOptions, TextureFormat};
+use crate::format::{ExtraFiles, FileContext, FormatHandler};
+use crate::header::{read_u32, Header};
+
+/// DDS format of an uncompressed texture, stored as 8-bit BGRA.
+const UNCOMPRESSED: u8 = 0x0A;
+
+/// File types of the texture headers.
+const TEXTURE_KINDS: [u32; 12] = [
+    0xA3, 0xA4, 0xA5, 0xA7, 0xAB, 0xAE, 0xB0, 0xB1, 0xB8, 0xBC, 0xC2, 0xC3,
+];
+
+/// Handler of the textures, decoded from their DDS form to the format of
+/// [`ExtractOptions::texture`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureHandler;
+
+impl FormatHandler for TextureHandler {
+    fn name(&self) -> &str {
+        "Texture"
+    }
+
+    fn detect(&self, file: &FileContext) -> Result<bool, ExtractError> {
+        if !file.node().name().ends_with(".png") {
+            return Ok(false);
+        }
+
+        // The texture detection of lotus-utils panics on truncated headers
+        Ok(TextureHeader::parse(file.header_data()?).is_some())
+    }
+
+    fn output_name(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<String, ExtractError> {
+        check_texture(file)?;
+        Ok(file_name(&file.node().name(), options.texture))
+    }
+
+    fn decode(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<Vec<u8>, ExtractError> {
+        let dds_format = check_texture(file)?;
+        convert(decompress(file)?, dds_format, options.texture)
+    }
+
+    fn decode_with_extras(
+        &self,
+        file: &FileContext,
+        options: &ExtractOptions,
+    ) -> Result<(Vec<u8>, ExtraFiles), ExtractError> {
+        if !options.keep_dds || options.texture == TextureFormat::Dds {
+            return Ok((self.decode(file, options)?, Vec::new()));
+        }
+
+        let dds_format = check_texture(file)?;
+        let data = decompress(file)?;
+        let image = convert(data.clone(), dds_format, options.texture)?;
+        let dds_name = file_name(&file.node().name(), TextureFormat::Dds);
+        Ok((image, vec![(dds_name, data)]))
+    }
+}
+
+/// Decompresses a texture to a DDS file.
+fn decompress(file: &FileContext) -> Result<Vec<u8>, ExtractError> {
+    // The texture decompression parses its own copy of the H payload
+    Ok(file
+        .package()
+        .decompress_texture(file.node())
+        .map_err(ExtractError::Decompression)?
+        .0)
+}
+
+/// Returns the name of a texture exported in the given format.
+pub(crate) fn file_name(name: &str, format: TextureFormat) -> String {
+    let stem = name.strip_suffix(".png").unwrap_or(name);
+    match format {
+        TextureFormat::Png => format!("{}.png", stem),
+        TextureFormat::Tga => format!("{}.tga", stem),
+        TextureFormat::Dds => format!("{}.dds", stem),
+    }
+}
+
+/// Converts the DDS file returned by `decompress_texture` to the given
+/// format, decoding the BCn blocks of the DDS format found in the texture
+/// header.
+///
+/// # Errors
+///
+/// Returns an error if the DDS file is malformed or the image cannot be
+/// encoded.
+pub(crate) fn convert(
+    data: Vec<u8>,
+    dds_format: u8,
+    format: TextureFormat,
+) -> Result<Vec<u8>, ExtractError> {
+    let image_format = match format {
+        TextureFormat::Dds => return Ok(data),
+        TextureFormat::Png => ImageFormat::Png,
+        TextureFormat::Tga => ImageFormat::Tga,
+    };
+
+    let image = decode(&data, dds_format)?;
+
+    let mut output = Cursor::new(Vec::new());
+    image
+        .write_to(&mut output, image_format)
+        .map_err(|err| ExtractError::Conversion(err.to_string()))?;
+
+    Ok(output.into_inner())
+}
+
+/// Decodes the first image of a DDS file to 8-bit RGBA.
+fn decode(data: &[u8], dds_format: u8) -> Result<RgbaImage, ExtractError> {
+    let dds = Dds::read(data).map_err(|err| ExtractError::Conversion(err.to_string()))?;
+    let width = dds.get_width() as usize;
+    let height = dds.get_height() as usize;
+
+    let decoder = match dds_format {
+        0x00 | 0x01 => texture2ddecoder::decode_bc1,
+        0x02 => texture2ddecoder::decode_bc2,
+        0x03 => texture2ddecoder::decode_bc3,
+        0x06 => texture2ddecoder::decode_bc4,
+        0x07 => texture2ddecoder::decode_bc5,
+        0x22 => texture2ddecoder::decode_bc7,
+        0x23 => texture2ddecoder::decode_bc6_unsigned,
+        UNCOMPRESSED => return bgra_image(&dds.data, width, height),
+        _ => {
+            return Err(ExtractError::UnknownFormat(format!(
+                "DDS format 0x{:02X}",
+                dds_format
+            )))
+        }
+    };
+
+    // The texture header rounds the number of blocks down, pad the data for
+    // the decoder which rounds it up
+    let block_len = match dds_format {
+        0x00 | 0x01 | 0x06 => 8,
+        _ => 16,
+    };
+    let mut blocks = dds.data;
+    blocks.resize(width.div_ceil(4) * height.div_ceil(4) * block_len, 0);
+
+    let mut pixels = vec![0u32; width * height];
+    decoder(&blocks, width, height, &mut pixels)
+        .map_err(|err| ExtractError::Conversion(err.to_string()))?;
+
+    // The decoders write BGRA pixels
+    let bgra: Vec<u8> = pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .collect();
+    bgra_image(&bgra, width, height)
+}
+
+/// Texture header following the common [`Header`] of the H cache entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextureHeader {
+    f_cache_image_count: u8,
+    dds_format: u8,
+    mip_map_count: u32,
+    width_ratio: u16,
+    height_ratio: u16,
+    max_side_length: u32,
+}
+
+impl TextureHeader {
+    /// Parses the header of a texture from the decompressed H cache data.
+    ///
+    /// Returns `None` if the data is not a texture header or is too short to
+    /// contain one.
+    fn parse(data: &[u8]) -> Option<Self> {
+        let header = Header::parse(data)?;
+        if !TEXTURE_KINDS.contains(&header.file_type) {
+            return None;
+        }
+
+        let offset = header.offset;
+        let f_cache_image_count = *data.get(offset + 1)?;
+        let dds_format = *data.get(offset + 3)?;
+        let mip_map_count = read_u32(data, offset + 4)?;
+
+        // Skip the F cache offsets of the mipmaps
+        let offset = (mip_map_count as usize)
+            .checked_mul(4)?
+            .checked_add(offset + 8)?;
+
+        let read_u16 = |offset: usize| -> Option<u16> {
+            Some(u16::from_le_bytes(
+                data.get(offset..offset + 2)?.try_into().ok()?,
+            ))
+        };
+        let width_ratio = read_u16(offset)?;
+        let height_ratio = read_u16(offset + 2)?;
+        let max_side_length = read_u32(data, offset + 8)?;
+
+        Some(Self {
+            f_cache_image_count,
+            dds_format,
+            mip_map_count,
+            width_ratio,
+            height_ratio,
+            max_side_length,
+        })
+    }
+
+    /// Returns the length of the image data in the F or B cache, as computed
+    /// by `decompress_texture`.
+    ///
+    /// Returns `None` if the dimensions are invalid, as the computation
+    /// overflows or divides by zero.
+    fn size(&self) -> Option<usize> {
+        let width_ratio = self.width_ratio as u32;
+        let height_ratio = self.height_ratio as u32;
+        let (width, height) = if width_ratio > height_ratio {
+            let height = self.max_side_length.checked_mul(height_ratio)? / width_ratio;
+            (self.max_side_length, height)
+        } else {
+            let width = self
+                .max_side_length
+                .checked_mul(width_ratio)?
+                .checked_div(height_ratio)?;
+            (width, self.max_side_length)
+        };
+
+        let bits_per_pixel: u32 = match self.dds_format {
+            0x00 | 0x01 | 0x06 => 8,
+            UNCOMPRESSED => 64,
+            _ => 16,
+        };
+        let size = (width >> 2)
+            .max(1)
+            .checked_mul((height >> 2).max(1))?
+            .checked_mul(bits_per_pixel)?;
+        Some(size as usize)
+    }
+}
+
+/// Checks that a texture can be decompressed, as the texture decompression
+/// panics on missing cache entries, truncated image data and invalid
+/// dimensions.
+///
+/// Returns the DDS format of the texture.
+fn check_texture(file: &FileContext) -> Result<u8, ExtractError> {
+    let header = TextureHeader::parse(file.header_data()?)
+        .ok_or_else(|| ExtractError::UnknownFormat(String::from("truncated texture header")))?;
+
+    if !matches!(
+        header.dds_format,
+        0x00..=0x03 | 0x06 | 0x07 | UNCOMPRESSED | 0x22 | 0x23
+    ) {
+        return Err(ExtractError::UnknownFormat(format!(
+            "DDS format 0x{:02X}",
+            header.dds_format
+        )));
+    }
+
+    let size = header
+        .size()
+        .ok_or_else(|| ExtractError::UnknownFormat(String::from("invalid texture dimensions")))?;
+
+    let package_type = match header.f_cache_image_count {
+        0 => PackageType::B,
+        _ => PackageType::F,
+    };
+    let data_node = cache_node(file.package(), package_type, file.node())?;
+
+    // The mipmaps stored in the F cache are decompressed block by block up to
+    // the size, the other images are sliced from the end of the entry
+    let sliced = package_type == PackageType::B || header.mip_map_count == 0;
+    if sliced && (data_node.len().max(0) as usize) < size {
+        return Err(ExtractError::Decompression(anyhow::anyhow!(
+            "{:?} cache entry shorter than the {} bytes of the image",
+            package_type,
+            size
+        )));
+    }
+
+    Ok(header.dds_format)
+}
+
+/// Creates an RGBA image from 8-bit BGRA pixels.
+fn bgra_image(bgra: &[u8], width: usize, height: usize) -> Result<RgbaImage, ExtractError> {
+    let rgba: Vec<u8> = bgra
+        .chunks_exact(4)
+        .take(width * height)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+
+    RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .ok_or_else(|| ExtractError::Conversion(String::from("truncated texture data")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_name("Icon.png", TextureFormat::Png), "Icon.png");
+        assert_eq!(file_name("Icon.png", TextureFormat::Tga), "Icon.tga");
+        assert_eq!(file_name("Icon.png", TextureFormat::Dds), "Icon.dds");
+    }
+
+    #[test]
+    fn header() {
+        let mut data = vec![0; 16];
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0xA3u32.to_le_bytes());
+        data.extend_from_slice(&[0, 1, 0, 0x22]);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[1, 0, 2, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&64u32.to_le_bytes());
+
+        let header = TextureHeader::parse(&data).unwrap();
+        assert_eq!(header.f_cache_image_count, 1);
+        assert_eq!(header.dds_format, 0x22);
+        assert_eq!(header.size(), Some(8 * 16 * 16));
+
+        assert_eq!(TextureHeader::parse(&data[..data.len() - 1]), None);
+
+        let header = TextureHeader {
+            width_ratio: 0,
+            height_ratio: 0,
+            ..header
+        };
+        assert_eq!(header.size(), None);
+    }
+
+    #[test]
+    fn bgra_pixels() {
+        let image = bgra_image(&[1, 2, 3, 4], 1, 1).unwrap();
+        assert_eq!(image.into_raw(), vec![3, 2, 1, 4]);
+
+        assert!(bgra_image(&[1, 2, 3], 1, 1).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use tokio::sync::oneshot;
+
+/// Value computed in the background for the node selected in a widget.
+///
+/// Decompressing and detecting formats can take a while, so the computation
+/// runs on the blocking thread pool instead of the runtime driving the UI, and
+/// its value is polled on each tick. Only the latest computation matters:
+/// starting another one or clearing drops the receiver of the previous one,
+/// which discards its value, skips it if it has not started yet and lets it
+/// stop early by checking whether it became stale.
+#[derive(Debug)]
+pub struct BackgroundLoad<T> {
+    value_rx: Option<oneshot::Receiver<T>>,
+}
+
+impl<T: Send + 'static> BackgroundLoad<T> {
+    pub fn new() -> Self {
+        Self { value_rx: None }
+    }
+
+    /// Returns whether a value is being computed.
+    #[inline]
+    pub fn is_loading(&self) -> bool {
+        self.value_rx.is_some()
+    }
+
+    /// Starts computing a value, discarding the previous one. The computation
+    /// is given a function returning whether it became stale.
+    pub fn start<F>(&mut self, load: F)
+    where
+        F: FnOnce(&dyn Fn() -> bool) -> T + Send + 'static,
+    {
+        let (value_tx, value_rx) = oneshot::channel();
+        self.value_rx = Some(value_rx);
+
+        tokio::task::spawn_blocking(move || {
+            if value_tx.is_closed() {
+                return;
+            }
+            let value = load(&|| value_tx.is_closed());
+            let _ = value_tx.send(value);
+        });
+    }
+
+    /// Discards the value being computed, if any.
+    pub fn clear(&mut self) {
+        self.value_rx = None;
+    }
+
+    /// Returns the value once it is computed.
+    pub fn poll(&mut self) -> Option<T> {
+        let value = match self.value_rx.as_mut()?.try_recv() {
+            Ok(value) => Some(value),
+            Err(oneshot::error::TryRecvError::Empty) => return None,
+            // The computation panicked
+            Err(oneshot::error::TryRecvError::Closed) => None,
+        };
+        self.value_rx = None;
+        value
+    }
+}
@@ -57,6 +57,10 @@ impl Button {
         self.active = active;
     }
 
+    pub fn set_active_label(&mut self, label: &str) {
+        self.active_label = Some(label.to_string());
+    }
+
     fn label(&self) -> &String {
         if self.active {
             self.active_label.as_ref().unwrap_or(&self.default_label)
@@ -1,42 +1,41 @@
-use std::path::PathBuf;
 use std::sync::Arc;
 
 use derivative::Derivative;
 use lotus_lib::cache_pair::CachePairReader;
-use lotus_lib::package::{Package, PackageType};
-use lotus_lib::toc::{DirectoryNode, Node, NodeKind};
+use lotus_lib::package::Package;
+use lotus_lib::toc::{Node, NodeKind};
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
 use ratatui::style::{Color, Style};
 use ratatui::text::{Span, Text};
 use ratatui::widgets::{Block, Borders, HighlightSpacing, List, ListState, WidgetRef};
+use wfcache_api::Navigator;
 
 use crate::action::Action;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct Explorer {
-    cwd: PathBuf,
-    #[derivative(Debug = "ignore")]
-    package: Arc<Package<CachePairReader>>,
+    navigator: Navigator,
     nodes: Vec<Node>,
     selected: usize,
 }
 
 impl Explorer {
-    pub fn new(package: Arc<Package<CachePairReader>>) -> Self {
+    pub fn new(package: Arc<Package<CachePairReader>>) -> wfcache_api::Result<Self> {
         let mut file_explorer = Self {
-            cwd: PathBuf::from("/"),
-            package,
+            navigator: Navigator::new(package)?,
             nodes: vec![],
             selected: 0,
         };
 
         file_explorer.get_and_set_files();
 
-        file_explorer
+        Ok(file_explorer)
     }
 
+    // Changing directory has side effects, it must not run in a match guard
+    #[allow(clippy::collapsible_match)]
     pub fn handle(&mut self, action: &Action) {
         match action {
             Action::NavigateUp => {
@@ -54,17 +53,19 @@ impl Explorer {
                 }
             }
             Action::NavigateOut => {
-                let parent = self.cwd.parent();
-
-                if let Some(parent) = parent {
-                    self.cwd = parent.to_path_buf();
+                if self.navigator.cwd().parent().is_some() && self.navigator.change_directory("..")
+                {
                     self.get_and_set_files();
                     self.selected = 0
                 }
             }
             Action::NavigateIn => {
-                if self.selected != 0 && self.nodes[self.selected].kind() == NodeKind::Directory {
-                    self.cwd = self.nodes.swap_remove(self.selected).path();
+                if self.selected != 0
+                    && self.nodes[self.selected].kind() == NodeKind::Directory
+                    && self
+                        .navigator
+                        .change_directory(self.nodes[self.selected].path())
+                {
                     self.get_and_set_files();
                     self.selected = 0
                 }
@@ -79,45 +80,20 @@ impl Explorer {
     }
 
     fn get_and_set_files(&mut self) {
-        let h_cache = self.package.borrow(PackageType::H).unwrap();
-        let current_directory = h_cache.get_directory_node(&self.cwd).unwrap();
-
-        let mut directories = Vec::new();
-        let mut files = Vec::new();
-
-        current_directory.children().iter().for_each(|node| {
-            if node.kind() == NodeKind::Directory {
-                directories.push(node.clone());
-            } else {
-                files.push(node.clone());
-            }
-        });
-
-        directories.sort_by(|a, b| a.name().cmp(&b.name()));
-        files.sort_by(|a, b| a.name().cmp(&b.name()));
+        let directories = self.navigator.directories();
+        let files = self.navigator.files();
+        let mut nodes = Vec::with_capacity(2 + directories.len() + files.len());
 
-        if let Some(_parent) = self.cwd.parent() {
-            let mut nodes = Vec::with_capacity(2 + directories.len() + files.len());
+        nodes.push(self.navigator.directory().clone());
 
-            let parent_node = current_directory.parent().unwrap();
-
-            nodes.push(current_directory);
+        if let Some(parent_node) = self.navigator.directory().parent() {
             nodes.push(parent_node);
+        }
 
-            nodes.extend(directories);
-            nodes.extend(files);
-
-            self.nodes = nodes;
-        } else {
-            let mut nodes = Vec::with_capacity(1 + directories.len() + files.len());
-
-            nodes.push(current_directory);
-
-            nodes.extend(directories);
-            nodes.extend(files);
+        nodes.extend_from_slice(directories);
+        nodes.extend_from_slice(files);
 
-            self.nodes = nodes;
-        }
+        self.nodes = nodes;
     }
 }
 
@@ -133,7 +109,7 @@ impl WidgetRef for Explorer {
         let nodes_text = self.nodes.iter().enumerate().map(|(index, node)| {
             if index == 0 {
                 Span::styled("./", NodeStyle::Directory).into()
-            } else if index == 1 && self.cwd.parent().is_some() {
+            } else if index == 1 && self.navigator.cwd().parent().is_some() {
                 Span::styled("../", NodeStyle::Directory).into()
             } else {
                 node.text()
@@ -149,7 +125,8 @@ impl WidgetRef for Explorer {
 
         let current_directory_name = format!(
             "/{}",
-            self.cwd
+            self.navigator
+                .cwd()
                 .file_name()
                 .and_then(|name| name.to_str())
                 .unwrap_or("")
@@ -21,6 +21,16 @@ impl Button {
     pub fn set_active(&mut self, active: bool) {
         self.button_widget.set_active(active);
     }
+
+    /// Shows that the extraction is being cancelled while it is still active.
+    pub fn set_cancelling(&mut self, cancelling: bool) {
+        let label = if cancelling {
+            "Cancelling…"
+        } else {
+            "Cancel"
+        };
+        self.button_widget.set_active_label(label);
+    }
 }
 
 impl WidgetRef for Button {
@@ -0,0 +1,103 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{Block, Borders, Clear, Padding, Paragraph, Widget, WidgetRef};
+use wfcache_api::extract::Plan;
+
+use super::gauge::show_bytes;
+
+/// Confirmation dialog listing what an extraction would write.
+#[derive(Debug, Default)]
+pub struct DryRun {
+    plan: Option<Plan>,
+    scroll: usize,
+}
+
+impl DryRun {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_plan(&mut self, plan: Plan) {
+        self.plan = Some(plan);
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        let len = self.plan.as_ref().map_or(0, |plan| plan.files.len());
+        self.scroll = (self.scroll + 1).min(len.saturating_sub(1));
+    }
+
+    fn summary(plan: &Plan) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::styled(
+            format!(
+                "{} files, {}",
+                plan.files.len(),
+                show_bytes(plan.total_bytes())
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        if plan.unchanged > 0 {
+            lines.push(Line::from(format!("{} unchanged files", plan.unchanged)));
+        }
+        if !plan.removed.is_empty() {
+            lines.push(Line::from(format!(
+                "{} files removed from the package",
+                plan.removed.len()
+            )));
+        }
+        lines.push(Line::default());
+        lines
+    }
+}
+
+impl WidgetRef for DryRun {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " Extract ".into(),
+            "<Enter> ".light_blue(),
+            "Cancel ".into(),
+            "<Esc> ".light_blue(),
+            "Scroll ".into(),
+            "<Up/Down> ".light_blue(),
+        ]);
+        let instructions = Title::from(instructions)
+            .alignment(Alignment::Center)
+            .position(Position::Bottom);
+
+        let block = Block::default()
+            .title(" Dry Run ")
+            .title(instructions)
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1));
+
+        let lines: Vec<Line> = match &self.plan {
+            None => vec![Line::from("Computing the files to extract...")],
+            Some(plan) => {
+                let mut lines = Self::summary(plan);
+                lines.extend(plan.files.iter().skip(self.scroll).map(
+                    |file| match &file.output_path {
+                        Ok(output_path) => Line::from(format!(
+                            "{} -> {}",
+                            file.path.display(),
+                            output_path.display()
+                        )),
+                        Err(err) => Line::styled(
+                            format!("{}: {}", file.path.display(), err),
+                            Style::default().fg(Color::Red),
+                        ),
+                    },
+                ));
+                lines
+            }
+        };
+
+        Clear.render(area, buf);
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::{Error, ErrorKind, Result};
+use std::io::Result;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -13,12 +13,20 @@ use ratatui::text::Line;
 use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::{Block, Borders, Widget, WidgetRef};
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{oneshot, watch};
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use wfcache_api::extract::{
+    collect_files, extract_files_to, plan_files, ExtractEvent, ExtractOptions, Plan, Progress,
+};
+use wfcache_api::sink::{self, Sink};
+use wfcache_api::Filter;
 
 use crate::action::Action;
-use crate::extract::{extract_dir, extract_file};
+use crate::widgets::LogEntry;
 
 use super::button::Button;
+use super::dry_run::DryRun;
 use super::gauge::Gauge;
 
 #[derive(Derivative)]
@@ -29,18 +37,36 @@ pub struct Extract {
     package: Arc<Package<CachePairReader>>,
     h_node: Node,
     recursive: bool,
+    options: ExtractOptions,
+    /// Filter expression being edited, if any
+    filter_input: Option<String>,
+    /// Sink of the output, opened by the first extraction and kept for the
+    /// whole session so that an archive is not replaced by the next one
+    #[derivative(Debug = "ignore")]
+    sink: Option<Arc<dyn Sink>>,
 
     button_widget: Button,
     gauge_widget: Gauge,
+    dry_run_widget: Option<DryRun>,
 
     extract_task: Option<JoinHandle<()>>,
-    extract_tx: Option<UnboundedSender<()>>,
-    progress_rx: UnboundedReceiver<(usize, usize)>,
-    progress_tx: UnboundedSender<(usize, usize)>,
+    plan_task: Option<JoinHandle<()>>,
+    /// Token cancelling the running extraction or dry run
+    cancellation_token: Option<CancellationToken>,
+    plan_rx: Option<oneshot::Receiver<Plan>>,
+    progress_rx: Option<watch::Receiver<Progress>>,
+    event_rx: UnboundedReceiver<ExtractEvent>,
+    event_tx: UnboundedSender<ExtractEvent>,
+    log_tx: UnboundedSender<LogEntry>,
 }
 
 impl Extract {
-    pub fn new<P>(package: Arc<Package<CachePairReader>>, output_dir: P) -> Self
+    pub fn new<P>(
+        package: Arc<Package<CachePairReader>>,
+        output_dir: P,
+        options: ExtractOptions,
+        log_tx: UnboundedSender<LogEntry>,
+    ) -> Self
     where
         P: Into<PathBuf>,
     {
@@ -52,19 +78,27 @@ impl Extract {
             .get_directory_node("/")
             .unwrap();
 
-        let (progress_tx, progress_rx) = unbounded_channel();
+        let (event_tx, event_rx) = unbounded_channel();
 
         Self {
             output_dir,
             package,
             h_node,
             recursive: false,
+            options,
+            filter_input: None,
+            sink: None,
             button_widget: Button::new(),
             gauge_widget: Gauge::new(),
+            dry_run_widget: None,
             extract_task: None,
-            extract_tx: None,
-            progress_rx,
-            progress_tx,
+            plan_task: None,
+            cancellation_token: None,
+            plan_rx: None,
+            progress_rx: None,
+            event_rx,
+            event_tx,
+            log_tx,
         }
     }
 
@@ -72,6 +106,18 @@ impl Extract {
         self.h_node = node.clone();
     }
 
+    /// Returns whether the filter field or the dry run dialog has the focus.
+    #[inline]
+    pub fn has_focus(&self) -> bool {
+        self.filter_input.is_some() || self.dry_run_widget.is_some()
+    }
+
+    /// Returns the dry run dialog, if open.
+    #[inline]
+    pub fn dry_run(&self) -> Option<&DryRun> {
+        self.dry_run_widget.as_ref()
+    }
+
     fn compute_layout(&self, area: Rect) -> (Rect, Rect) {
         let extract_layout = Layout::horizontal([
             Constraint::Length(15),
@@ -87,7 +133,25 @@ impl Extract {
         match action {
             Action::ExtractToggle => self.toggle_extract()?,
             Action::RecursiveModeToggle => self.toggle_recursive(),
-            Action::Tick => self.update_progress(),
+            Action::OverwritePolicyToggle => self.toggle_overwrite_policy(),
+            Action::RawModeToggle => self.options.raw = !self.options.raw,
+            Action::AudioFormatToggle => self.options.audio = self.options.audio.next(),
+            Action::TextureFormatToggle => self.options.texture = self.options.texture.next(),
+            Action::FilterEdit => self.filter_input = Some(self.options.filter.to_string()),
+            Action::DryRun => self.open_dry_run(),
+            Action::InputChar(_)
+            | Action::InputBackspace
+            | Action::InputConfirm
+            | Action::InputCancel
+            | Action::InputUp
+            | Action::InputDown => {
+                if self.dry_run_widget.is_some() {
+                    self.handle_dry_run(action)?;
+                } else {
+                    self.edit_filter(action);
+                }
+            }
+            Action::Tick => self.receive_events(),
             _ => {}
         }
         Ok(())
@@ -95,36 +159,115 @@ impl Extract {
 
     fn toggle_extract(&mut self) -> Result<()> {
         if self.extract_task.is_none() {
+            let sink = match &self.sink {
+                Some(sink) => sink.clone(),
+                None => match sink::open(&self.output_dir) {
+                    Ok(sink) => self.sink.insert(Arc::from(sink)).clone(),
+                    Err(err) => {
+                        let _ = self.log_tx.send(LogEntry::error(err.to_string()));
+                        return Ok(());
+                    }
+                },
+            };
+
             self.button_widget.set_active(true);
 
-            let (extract_tx, mut extract_rx) = unbounded_channel();
-            self.extract_tx = Some(extract_tx);
+            // Stop the dry run if it is still planning
+            if let Some(cancellation_token) = self.cancellation_token.take() {
+                cancellation_token.cancel();
+            }
+            let cancellation_token = CancellationToken::new();
+            self.cancellation_token = Some(cancellation_token.clone());
+
+            // Only the latest progress matters, older values are overwritten
+            let (progress_tx, progress_rx) = watch::channel(Progress::default());
+            self.progress_rx = Some(progress_rx);
 
             let package = self.package.clone();
             let h_node = self.h_node.clone();
             let output_dir = self.output_dir.clone();
             let recursive = self.recursive;
-            let progress_tx = self.progress_tx.clone();
-            self.extract_task = Some(tokio::spawn(async move {
-                extract(
-                    package,
-                    h_node,
-                    output_dir,
-                    recursive,
-                    &mut extract_rx,
-                    progress_tx,
+            let options = self.options.clone();
+            let event_tx = self.event_tx.clone();
+
+            // The extraction is blocking, keep it off the runtime driving the UI
+            self.extract_task = Some(tokio::task::spawn_blocking(move || {
+                let files = collect(&package, h_node, recursive, &options);
+
+                extract_files_to(
+                    &package,
+                    &files,
+                    &output_dir,
+                    sink.as_ref(),
+                    &options,
+                    &cancellation_token,
+                    |event| match event {
+                        ExtractEvent::Progress(progress) => {
+                            progress_tx.send_replace(progress);
+                        }
+                        event => {
+                            let _ = event_tx.send(event);
+                        }
+                    },
                 );
             }));
-        } else {
-            self.extract_tx
-                .take()
-                .unwrap()
-                .send(())
-                .map_err(|_| Error::new(ErrorKind::Other, "Failed to send cancel signal"))?;
+        } else if let Some(cancellation_token) = self.cancellation_token.take() {
+            // The task is kept until it stops so that no other extraction
+            // starts alongside it
+            cancellation_token.cancel();
+            self.button_widget.set_cancelling(true);
+        }
 
-            self.extract_task = None;
-            self.extract_tx = None;
-            self.button_widget.set_active(false);
+        Ok(())
+    }
+
+    fn open_dry_run(&mut self) {
+        // A cancelled dry run may still be stopping
+        if self.extract_task.is_some() || self.plan_task.is_some() {
+            return;
+        }
+
+        let (plan_tx, plan_rx) = oneshot::channel();
+        self.plan_rx = Some(plan_rx);
+        self.dry_run_widget = Some(DryRun::new());
+
+        let cancellation_token = CancellationToken::new();
+        self.cancellation_token = Some(cancellation_token.clone());
+
+        let package = self.package.clone();
+        let h_node = self.h_node.clone();
+        let output_dir = self.output_dir.clone();
+        let recursive = self.recursive;
+        let options = self.options.clone();
+
+        self.plan_task = Some(tokio::task::spawn_blocking(move || {
+            let files = collect(&package, h_node, recursive, &options);
+            let plan = plan_files(&package, &files, &output_dir, &options, &cancellation_token);
+            let _ = plan_tx.send(plan);
+        }));
+    }
+
+    fn handle_dry_run(&mut self, action: &Action) -> Result<()> {
+        let Some(dry_run_widget) = self.dry_run_widget.as_mut() else {
+            return Ok(());
+        };
+
+        match action {
+            Action::InputConfirm | Action::InputChar('y') => {
+                self.dry_run_widget = None;
+                self.plan_rx = None;
+                self.toggle_extract()?;
+            }
+            Action::InputCancel | Action::InputChar('n') | Action::InputChar('q') => {
+                self.dry_run_widget = None;
+                self.plan_rx = None;
+                if let Some(cancellation_token) = self.cancellation_token.take() {
+                    cancellation_token.cancel();
+                }
+            }
+            Action::InputUp => dry_run_widget.scroll_up(),
+            Action::InputDown => dry_run_widget.scroll_down(),
+            _ => {}
         }
 
         Ok(())
@@ -134,23 +277,137 @@ impl Extract {
         self.recursive = !self.recursive;
     }
 
-    fn update_progress(&mut self) {
-        while let Ok((count, progress)) = self.progress_rx.try_recv() {
-            self.gauge_widget.set_progress(count, progress);
+    fn toggle_overwrite_policy(&mut self) {
+        self.options.overwrite = self.options.overwrite.next();
+    }
+
+    fn edit_filter(&mut self, action: &Action) {
+        let Some(filter_input) = self.filter_input.as_mut() else {
+            return;
+        };
 
-            if count == progress {
-                self.extract_task = None;
-                self.extract_tx = None;
-                self.button_widget.set_active(false);
+        match action {
+            Action::InputChar(c) => filter_input.push(*c),
+            Action::InputBackspace => {
+                filter_input.pop();
             }
+            Action::InputConfirm => match Filter::parse(filter_input) {
+                Ok(filter) => {
+                    self.options.filter = filter;
+                    self.filter_input = None;
+                }
+                Err(err) => {
+                    let _ = self.log_tx.send(LogEntry::error(err.to_string()));
+                }
+            },
+            Action::InputCancel => self.filter_input = None,
+            _ => {}
+        }
+    }
+
+    fn receive_events(&mut self) {
+        // Checked first so that the final progress is not missed
+        let finished = self
+            .extract_task
+            .as_ref()
+            .is_some_and(JoinHandle::is_finished);
+
+        if self.plan_task.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.plan_task = None;
+            if self.extract_task.is_none() {
+                self.cancellation_token = None;
+            }
+        }
+
+        if let Some(plan_rx) = self.plan_rx.as_mut() {
+            if let Ok(plan) = plan_rx.try_recv() {
+                if let Some(dry_run_widget) = self.dry_run_widget.as_mut() {
+                    dry_run_widget.set_plan(plan);
+                }
+                self.plan_rx = None;
+            }
+        }
+
+        while let Ok(event) = self.event_rx.try_recv() {
+            match event {
+                ExtractEvent::Progress(_) => {}
+                ExtractEvent::Extracted { path, output_path } => {
+                    let _ = self.log_tx.send(LogEntry::success(format!(
+                        "{} -> {}",
+                        path.display(),
+                        output_path.display()
+                    )));
+                }
+                ExtractEvent::Skipped { path, output_path } => {
+                    let _ = self.log_tx.send(LogEntry::info(format!(
+                        "{} -> {} (skipped)",
+                        path.display(),
+                        output_path.display()
+                    )));
+                }
+                ExtractEvent::Removed { path } => {
+                    let _ = self.log_tx.send(LogEntry::info(format!(
+                        "{} was removed from the package",
+                        path.display()
+                    )));
+                }
+                ExtractEvent::Manifest { manifest_path } => {
+                    let _ = self.log_tx.send(LogEntry::info(format!(
+                        "Manifest written to {}",
+                        manifest_path.display()
+                    )));
+                }
+                ExtractEvent::Failed { path, error } => {
+                    let _ =
+                        self.log_tx
+                            .send(LogEntry::error(format!("{}: {}", path.display(), error)));
+                }
+            }
+        }
+
+        if let Some(progress_rx) = self.progress_rx.as_mut() {
+            if progress_rx.has_changed().unwrap_or(true) {
+                self.gauge_widget
+                    .set_progress(&progress_rx.borrow_and_update());
+            }
+        }
+
+        if finished {
+            self.extract_task = None;
+            self.cancellation_token = None;
+            self.progress_rx = None;
+            self.button_widget.set_active(false);
+            self.button_widget.set_cancelling(false);
+        }
+    }
+}
+
+impl Extract {
+    /// Stops the running extraction, if any, and finishes writing the output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output cannot be finished, e.g. the end of an
+    /// archive cannot be written.
+    pub async fn finish(&mut self) -> wfcache_api::Result<()> {
+        if let Some(cancellation_token) = self.cancellation_token.take() {
+            cancellation_token.cancel();
+        }
+        if let Some(extract_task) = self.extract_task.take() {
+            let _ = extract_task.await;
+        }
+
+        match self.sink.take() {
+            Some(sink) => Ok(sink.finish()?),
+            None => Ok(()),
         }
     }
 }
 
 impl Drop for Extract {
     fn drop(&mut self) {
-        if let Some(extract_tx) = self.extract_tx.take() {
-            let _ = extract_tx.send(());
+        if let Some(cancellation_token) = self.cancellation_token.take() {
+            cancellation_token.cancel();
         }
     }
 }
@@ -163,11 +420,34 @@ impl WidgetRef for Extract {
             "Recursive Disabled "
         };
 
+        let overwrite_text = format!("Overwrite {} ", self.options.overwrite);
+
+        let raw_mode_text = if self.options.raw {
+            "Raw Enabled "
+        } else {
+            "Raw Disabled "
+        };
+
+        let audio_format_text = format!("Audio {} ", self.options.audio);
+        let texture_format_text = format!("Texture {} ", self.options.texture);
+
         let instructions = Line::from(vec![
             " Extract ".into(),
             "<Space> ".light_blue(),
             recursive_mode_text.into(),
             "<R> ".light_blue(),
+            overwrite_text.into(),
+            "<O> ".light_blue(),
+            raw_mode_text.into(),
+            "<P> ".light_blue(),
+            audio_format_text.into(),
+            "<A> ".light_blue(),
+            texture_format_text.into(),
+            "<T> ".light_blue(),
+            "Dry Run ".into(),
+            "<D> ".light_blue(),
+            "Log ".into(),
+            "<V> ".light_blue(),
             "Quit ".into(),
             "<Q> ".light_blue(),
         ]);
@@ -175,7 +455,27 @@ impl WidgetRef for Extract {
             .alignment(Alignment::Center)
             .position(Position::Bottom);
 
+        let filter = match &self.filter_input {
+            Some(filter_input) => Line::from(vec![
+                " Filter: ".into(),
+                format!("{}_ ", filter_input).yellow(),
+                "Apply ".into(),
+                "<Enter> ".light_blue(),
+                "Cancel ".into(),
+                "<Esc> ".light_blue(),
+            ]),
+            None if self.options.filter.is_empty() => {
+                Line::from(vec![" Filter ".into(), "<F> ".light_blue()])
+            }
+            None => Line::from(vec![
+                format!(" Filter: {} ", self.options.filter).into(),
+                "<F> ".light_blue(),
+            ]),
+        };
+        let filter = Title::from(filter).alignment(Alignment::Left);
+
         Block::default()
+            .title(filter)
             .title(instructions)
             .borders(Borders::ALL)
             .render(area, buf);
@@ -214,23 +514,18 @@ impl WidgetRef for Extract {
 //     }
 // }
 
-fn extract(
-    package: Arc<Package<CachePairReader>>,
+/// Collects the files to extract from a node: the node itself if it is a file,
+/// or the files of the directory matching the filter.
+fn collect(
+    package: &Package<CachePairReader>,
     node: Node,
-    output_dir: PathBuf,
     recursive: bool,
-    extract_rx: &mut UnboundedReceiver<()>,
-    progress_tx: UnboundedSender<(usize, usize)>,
-) {
+    options: &ExtractOptions,
+) -> Vec<Node> {
     match node.kind() {
-        NodeKind::Directory => extract_dir(
-            &package,
-            &node,
-            &output_dir,
-            recursive,
-            extract_rx,
-            progress_tx,
-        ),
-        NodeKind::File => extract_file(&package, &node, &output_dir, 0, 1, progress_tx),
+        NodeKind::Directory => options
+            .filter
+            .apply(package, collect_files(&node, recursive)),
+        NodeKind::File => vec![node],
     }
 }
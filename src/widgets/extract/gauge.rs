@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
 use ratatui::style::{Color, Style};
 use ratatui::widgets::{Block, Gauge as GaugeWidget, Widget, WidgetRef};
+use wfcache_api::extract::Progress;
 
 #[derive(Debug, Clone)]
 pub struct Gauge {
@@ -17,15 +20,40 @@ impl Gauge {
         }
     }
 
-    pub fn set_progress(&mut self, count: usize, total: usize) {
-        assert!(count <= total);
-        if total == 0 {
+    pub fn set_progress(&mut self, progress: &Progress) {
+        assert!(progress.count <= progress.total);
+        if progress.total == 0 {
             self.ratio = 0.0;
             self.label = String::from("0/0");
             return;
         }
-        self.ratio = count as f64 / total as f64;
-        self.label = format!("{}/{}", count, total);
+        self.ratio = progress.ratio().clamp(0.0, 1.0);
+
+        let mut label = format!(
+            "{}/{}",
+            show_bytes(progress.bytes),
+            show_bytes(progress.total_bytes)
+        );
+        if !progress.is_done() {
+            label.push_str(&format!(
+                " - {}/s",
+                show_bytes(progress.throughput() as u64)
+            ));
+            if let Some(eta) = progress.eta() {
+                label.push_str(&format!(" - ETA {}", show_duration(eta)));
+            }
+        }
+        label.push_str(&format!(" - {}/{} files", progress.count, progress.total));
+        if progress.unchanged > 0 {
+            label.push_str(&format!(" ({} unchanged)", progress.unchanged));
+        }
+        if progress.skipped > 0 {
+            label.push_str(&format!(" ({} skipped)", progress.skipped));
+        }
+        if progress.failed > 0 {
+            label.push_str(&format!(" ({} failed)", progress.failed));
+        }
+        self.label = label;
     }
 }
 
@@ -41,3 +69,28 @@ impl WidgetRef for Gauge {
             .render(area, buf);
     }
 }
+
+#[inline]
+pub(super) fn show_bytes(bytes: u64) -> String {
+    if bytes < 1_000 {
+        format!("{} B", bytes)
+    } else if bytes < 1_000_000 {
+        format!("{:.2} KB", bytes as f64 / 1_000.0)
+    } else if bytes < 1_000_000_000 {
+        format!("{:.2} MB", bytes as f64 / 1_000_000.0)
+    } else {
+        format!("{:.2} GB", bytes as f64 / 1_000_000_000.0)
+    }
+}
+
+#[inline]
+fn show_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 3600 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
@@ -1,4 +1,5 @@
 mod button;
+mod dry_run;
 mod extract;
 mod gauge;
 
@@ -0,0 +1,238 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use derivative::Derivative;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::{Package, PackageType};
+use lotus_lib::toc::{Node, NodeKind};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{Block, Borders, Padding, Paragraph, Widget, WidgetRef};
+use wfcache_api::extract::read_payload_prefix;
+
+use crate::action::Action;
+use crate::widgets::background::BackgroundLoad;
+
+/// Number of bytes of a payload shown in the dump.
+const PREVIEW_LEN: usize = 64 * 1024;
+/// Number of bytes shown on each row.
+const ROW_LEN: usize = 16;
+
+/// First bytes of a payload and its length, or the error reading it.
+type Preview = Result<(Vec<u8>, usize), String>;
+
+/// Hex dump of the first bytes of the decompressed H, F or B payload of the
+/// selected file.
+///
+/// The payload is only read while the pane is visible, in a background task,
+/// decompressing no more of it than the dump shows when the cache allows it.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Hex {
+    #[derivative(Debug = "ignore")]
+    package: Arc<Package<CachePairReader>>,
+    visible: bool,
+
+    node: Option<Node>,
+    package_type: PackageType,
+    data: Option<Preview>,
+    data_load: BackgroundLoad<Preview>,
+    /// Index of the first row shown
+    scroll: usize,
+    /// Number of rows shown by the last render, used to scroll by pages
+    #[derivative(Debug = "ignore")]
+    page: Cell<usize>,
+}
+
+impl Hex {
+    pub fn new(package: Arc<Package<CachePairReader>>) -> Self {
+        Self {
+            package,
+            visible: false,
+            node: None,
+            package_type: PackageType::H,
+            data: None,
+            data_load: BackgroundLoad::new(),
+            scroll: 0,
+            page: Cell::new(1),
+        }
+    }
+
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn handle(&mut self, action: &Action) {
+        match action {
+            Action::HexToggle => {
+                self.visible = !self.visible;
+                self.load();
+            }
+            Action::Tick => self.receive(),
+            _ => {}
+        }
+
+        if !self.visible {
+            return;
+        }
+
+        let page = self.page.get().max(1);
+        match action {
+            Action::HexCacheToggle => {
+                self.package_type = match self.package_type {
+                    PackageType::H => PackageType::F,
+                    PackageType::F => PackageType::B,
+                    PackageType::B => PackageType::H,
+                };
+                self.reset();
+                self.load();
+            }
+            Action::ScrollDown => self.scroll_to(self.scroll + 1),
+            Action::ScrollUp => self.scroll_to(self.scroll.saturating_sub(1)),
+            Action::PageDown => self.scroll_to(self.scroll + page),
+            Action::PageUp => self.scroll_to(self.scroll.saturating_sub(page)),
+            _ => {}
+        }
+    }
+
+    pub fn set_node(&mut self, node: &Node) {
+        let node = (node.kind() == NodeKind::File).then(|| node.clone());
+        if self.node.as_ref().map(Node::path) == node.as_ref().map(Node::path) {
+            return;
+        }
+
+        self.node = node;
+        self.reset();
+        self.load();
+    }
+
+    fn reset(&mut self) {
+        self.data = None;
+        self.data_load.clear();
+        self.scroll = 0;
+    }
+
+    /// Starts reading the payload of the node if it is shown and not read yet.
+    fn load(&mut self) {
+        if !self.visible || self.data.is_some() || self.data_load.is_loading() {
+            return;
+        }
+        let Some(node) = self.node.clone() else {
+            return;
+        };
+
+        let package = self.package.clone();
+        let package_type = self.package_type;
+        self.data_load.start(move |_| {
+            read_payload_prefix(&package, &node, package_type, PREVIEW_LEN)
+                .map_err(|err| err.to_string())
+        });
+    }
+
+    fn receive(&mut self) {
+        if let Some(data) = self.data_load.poll() {
+            self.data = Some(data);
+        }
+    }
+
+    fn scroll_to(&mut self, scroll: usize) {
+        let rows = match &self.data {
+            Some(Ok((data, _))) => data.len().div_ceil(ROW_LEN),
+            _ => 0,
+        };
+        self.scroll = scroll.min(rows.saturating_sub(1));
+    }
+}
+
+impl WidgetRef for Hex {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " Cache ".into(),
+            "<Z> ".light_blue(),
+            "Scroll ".into(),
+            "<J/K> ".light_blue(),
+            "Page ".into(),
+            "<[/]> ".light_blue(),
+            "Hide ".into(),
+            "<X> ".light_blue(),
+        ]);
+        let instructions = Title::from(instructions)
+            .alignment(Alignment::Center)
+            .position(Position::Bottom);
+
+        let title = match &self.data {
+            Some(Ok((data, len))) if data.len() < *len => format!(
+                " Hex ({:?} cache, first {} of {} bytes) ",
+                self.package_type,
+                data.len(),
+                len
+            ),
+            Some(Ok((_, len))) => format!(" Hex ({:?} cache, {} bytes) ", self.package_type, len),
+            _ => format!(" Hex ({:?} cache) ", self.package_type),
+        };
+
+        let block = Block::default()
+            .title(title)
+            .title(instructions)
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1));
+
+        let height = block.inner(area).height as usize;
+        self.page.set(height);
+
+        let lines = match (&self.node, &self.data) {
+            (None, _) => vec![Line::from("No file selected")],
+            (_, Some(Err(err))) => vec![Line::styled(err.clone(), Style::default().fg(Color::Red))],
+            (_, Some(Ok((data, _)))) if data.is_empty() => vec![Line::from("Empty payload")],
+            (_, Some(Ok((data, _)))) => data
+                .chunks(ROW_LEN)
+                .enumerate()
+                .skip(self.scroll)
+                .take(height)
+                .map(|(row, bytes)| dump_line(row * ROW_LEN, bytes))
+                .collect(),
+            (_, None) => vec![Line::from("Loading...")],
+        };
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+/// Formats a row of the dump: the offset, the bytes in hex and their
+/// printable ASCII characters.
+fn dump_line(offset: usize, bytes: &[u8]) -> Line<'static> {
+    let mut hex = String::with_capacity(ROW_LEN * 3 + 1);
+    for i in 0..ROW_LEN {
+        if i == ROW_LEN / 2 {
+            hex.push(' ');
+        }
+        match bytes.get(i) {
+            Some(byte) => hex.push_str(&format!("{:02X} ", byte)),
+            None => hex.push_str("   "),
+        }
+    }
+
+    let ascii: String = bytes
+        .iter()
+        .map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+
+    Line::from(vec![
+        Span::styled(
+            format!("{:08X}  ", offset),
+            Style::default().fg(Color::LightBlue),
+        ),
+        hex.into(),
+        Span::styled(format!(" {}", ascii), Style::default().fg(Color::Gray)),
+    ])
+}
@@ -0,0 +1,3 @@
+mod hex;
+
+pub use hex::Hex;
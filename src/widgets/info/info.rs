@@ -9,28 +9,45 @@ use ratatui::layout::Rect;
 use ratatui::style::{Color, Style, Stylize};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Padding, Paragraph, Widget, WidgetRef, Wrap};
+use wfcache_api::format::{FileContext, Registry};
+
+use crate::action::Action;
+use crate::widgets::background::BackgroundLoad;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct Info {
     #[derivative(Debug = "ignore")]
     package: Arc<Package<CachePairReader>>,
+    handlers: Arc<Registry>,
 
     h_node: Node,
     f_node: Option<Node>,
     b_node: Option<Node>,
+    /// Name of the format handler the selected file would be extracted with
+    handler: Option<String>,
+    handler_load: BackgroundLoad<String>,
 }
 
 impl Info {
-    pub fn new(package: Arc<Package<CachePairReader>>) -> Self {
+    pub fn new(package: Arc<Package<CachePairReader>>, handlers: Arc<Registry>) -> Self {
         let h_cache = package.borrow(PackageType::H).unwrap();
         let h_node = h_cache.get_directory_node("/").unwrap();
 
         Self {
             package,
+            handlers,
             h_node,
             f_node: None,
             b_node: None,
+            handler: None,
+            handler_load: BackgroundLoad::new(),
+        }
+    }
+
+    pub fn handle(&mut self, action: &Action) {
+        if let Action::Tick = action {
+            self.receive();
         }
     }
 
@@ -49,9 +66,33 @@ impl Info {
             if let Some(b_cache) = self.package.borrow(PackageType::B) {
                 self.b_node = b_cache.get_file_node(&node_path);
             }
+            self.detect_handler(node.clone());
         } else {
             self.f_node = None;
             self.b_node = None;
+            self.handler = None;
+            self.handler_load.clear();
+        }
+    }
+
+    /// Starts finding out the format handler of a file node.
+    fn detect_handler(&mut self, node: Node) {
+        self.handler = None;
+
+        let package = self.package.clone();
+        let handlers = self.handlers.clone();
+        self.handler_load.start(move |_| {
+            let file = FileContext::new(&package, node);
+            match handlers.find(&file) {
+                Ok(handler) => handler.name().to_string(),
+                Err(err) => format!("Unknown ({})", err),
+            }
+        });
+    }
+
+    fn receive(&mut self) {
+        if let Some(handler) = self.handler_load.poll() {
+            self.handler = Some(handler);
         }
     }
 }
@@ -65,6 +106,12 @@ impl WidgetRef for Info {
             Line::from(vec![node_path.into()]),
         ];
 
+        if let Some(handler) = &self.handler {
+            content.push(Line::from(format!("Handler: {}", handler)));
+        } else if self.handler_load.is_loading() {
+            content.push(Line::from("Handler: detecting..."));
+        }
+
         let cache_style = Style::new().fg(Color::LightBlue).underlined();
 
         if self.h_node.kind() == NodeKind::File {
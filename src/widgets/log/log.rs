@@ -0,0 +1,219 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{Block, Borders, Padding, Paragraph, Widget, WidgetRef};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::action::Action;
+
+const LOG_FILE_NAME: &str = "extract.log";
+/// Number of entries kept, the oldest ones are dropped first.
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Info,
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    level: Level,
+    message: String,
+}
+
+impl LogEntry {
+    pub fn info<S: Into<String>>(message: S) -> Self {
+        Self {
+            level: Level::Info,
+            message: message.into(),
+        }
+    }
+
+    pub fn success<S: Into<String>>(message: S) -> Self {
+        Self {
+            level: Level::Success,
+            message: message.into(),
+        }
+    }
+
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.into(),
+        }
+    }
+
+    fn prefix(&self) -> &'static str {
+        match self.level {
+            Level::Info => "[INFO] ",
+            Level::Success => "[OK]   ",
+            Level::Error => "[FAIL] ",
+        }
+    }
+
+    fn style(&self) -> Style {
+        match self.level {
+            Level::Info => Style::default(),
+            Level::Success => Style::default().fg(Color::Green),
+            Level::Error => Style::default().fg(Color::Red),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Log {
+    output_dir: PathBuf,
+    entries: VecDeque<LogEntry>,
+    /// Number of lines scrolled up from the most recent entry
+    scroll: usize,
+    /// Number of lines shown by the last render, used to scroll by pages
+    page: Cell<usize>,
+    visible: bool,
+
+    log_rx: UnboundedReceiver<LogEntry>,
+    log_tx: UnboundedSender<LogEntry>,
+}
+
+impl Log {
+    pub fn new<P>(output_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        let (log_tx, log_rx) = unbounded_channel();
+
+        Self {
+            output_dir: output_dir.into(),
+            entries: VecDeque::with_capacity(MAX_ENTRIES),
+            scroll: 0,
+            page: Cell::new(1),
+            visible: false,
+            log_rx,
+            log_tx,
+        }
+    }
+
+    /// Returns a sender to push entries to the log from other widgets or tasks.
+    pub fn sender(&self) -> UnboundedSender<LogEntry> {
+        self.log_tx.clone()
+    }
+
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn handle(&mut self, action: &Action) {
+        match action {
+            Action::LogToggle => self.visible = !self.visible,
+            Action::Tick => self.receive(),
+            _ => {}
+        }
+
+        if !self.visible {
+            return;
+        }
+
+        let page = self.page.get().max(1);
+        match action {
+            Action::LogClear => {
+                self.entries.clear();
+                self.scroll = 0;
+            }
+            Action::LogSave => self.save(),
+            Action::LogScrollUp => {
+                self.scroll = (self.scroll + page).min(self.entries.len().saturating_sub(1))
+            }
+            Action::LogScrollDown => self.scroll = self.scroll.saturating_sub(page),
+            _ => {}
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(entry) = self.log_rx.try_recv() {
+            self.push(entry);
+
+            // Keep the same lines in view when scrolled up
+            if self.scroll > 0 {
+                self.scroll = (self.scroll + 1).min(self.entries.len().saturating_sub(1));
+            }
+        }
+    }
+
+    fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    fn save(&mut self) {
+        let path = self.output_dir.join(LOG_FILE_NAME);
+
+        let result = std::fs::create_dir_all(&self.output_dir)
+            .and_then(|_| std::fs::File::create(&path))
+            .and_then(|mut file| {
+                self.entries
+                    .iter()
+                    .try_for_each(|entry| writeln!(file, "{}{}", entry.prefix(), entry.message))
+            });
+
+        self.push(match result {
+            Ok(()) => LogEntry::info(format!("Log saved to {}", path.display())),
+            Err(err) => {
+                LogEntry::error(format!("Failed to save log to {}: {}", path.display(), err))
+            }
+        });
+        self.scroll = 0;
+    }
+}
+
+impl WidgetRef for Log {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let instructions = Line::from(vec![
+            " Clear ".into(),
+            "<C> ".light_blue(),
+            "Save ".into(),
+            "<S> ".light_blue(),
+            "Scroll ".into(),
+            "<PgUp/PgDn> ".light_blue(),
+            "Hide ".into(),
+            "<V> ".light_blue(),
+        ]);
+        let instructions = Title::from(instructions)
+            .alignment(Alignment::Center)
+            .position(Position::Bottom);
+
+        let block = Block::default()
+            .title(format!(" Log ({}) ", self.entries.len()))
+            .title(instructions)
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1));
+
+        let height = block.inner(area).height as usize;
+        self.page.set(height);
+        let end = self.entries.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(height);
+
+        let lines: Vec<Line> = self
+            .entries
+            .range(start..end)
+            .map(|entry| {
+                Line::styled(
+                    format!("{}{}", entry.prefix(), entry.message),
+                    entry.style(),
+                )
+            })
+            .collect();
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
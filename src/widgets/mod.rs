@@ -1,8 +1,15 @@
+mod background;
 mod button;
 mod explorer;
 mod extract;
+mod hex;
 mod info;
+mod log;
+mod preview;
 
 pub use explorer::Explorer;
 pub use extract::Extract;
+pub use hex::Hex;
 pub use info::Info;
+pub use log::{Log, LogEntry};
+pub use preview::Preview;
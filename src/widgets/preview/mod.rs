@@ -0,0 +1,3 @@
+mod preview;
+
+pub use preview::Preview;
@@ -0,0 +1,263 @@
+use std::cell::Cell;
+use std::sync::Arc;
+
+use derivative::Derivative;
+use lotus_lib::cache_pair::CachePairReader;
+use lotus_lib::package::Package;
+use lotus_lib::toc::{Node, NodeKind};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Alignment, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{Block, Borders, Padding, Paragraph, Widget, WidgetRef};
+use wfcache_api::format::{FileContext, FormatHandler, TextHandler};
+
+use crate::action::Action;
+use crate::widgets::background::BackgroundLoad;
+
+/// Scrollable preview of the selected file when it holds text.
+///
+/// The start of the file is checked for text in a background task, which only
+/// decompresses the whole file if it is text. The preview is only shown once
+/// its text is known.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Preview {
+    #[derivative(Debug = "ignore")]
+    package: Arc<Package<CachePairReader>>,
+
+    node: Option<Node>,
+    #[derivative(Debug = "ignore")]
+    lines: Vec<String>,
+    lines_load: BackgroundLoad<Option<Vec<String>>>,
+    /// Index of the first line shown
+    scroll: usize,
+    /// Number of lines shown by the last render, used to scroll by pages
+    #[derivative(Debug = "ignore")]
+    page: Cell<usize>,
+
+    /// Search being edited, if any
+    search_input: Option<String>,
+    /// Lowercase search of which the matches are highlighted
+    search: String,
+}
+
+impl Preview {
+    pub fn new(package: Arc<Package<CachePairReader>>) -> Self {
+        Self {
+            package,
+            node: None,
+            lines: Vec::new(),
+            lines_load: BackgroundLoad::new(),
+            scroll: 0,
+            page: Cell::new(1),
+            search_input: None,
+            search: String::new(),
+        }
+    }
+
+    /// Returns whether the selected file holds text.
+    #[inline]
+    pub fn is_visible(&self) -> bool {
+        !self.lines.is_empty()
+    }
+
+    /// Returns whether the search field has the focus.
+    #[inline]
+    pub fn has_focus(&self) -> bool {
+        self.search_input.is_some()
+    }
+
+    pub fn handle(&mut self, action: &Action) {
+        if let Action::Tick = action {
+            self.receive();
+        }
+
+        if !self.is_visible() {
+            return;
+        }
+
+        if self.search_input.is_some() {
+            self.edit_search(action);
+            return;
+        }
+
+        let page = self.page.get().max(1);
+        match action {
+            Action::ScrollDown => self.scroll_to(self.scroll + 1),
+            Action::ScrollUp => self.scroll_to(self.scroll.saturating_sub(1)),
+            Action::PageDown => self.scroll_to(self.scroll + page),
+            Action::PageUp => self.scroll_to(self.scroll.saturating_sub(page)),
+            Action::SearchEdit => self.search_input = Some(String::new()),
+            Action::SearchNext => self.find(self.scroll + 1, false),
+            Action::SearchPrevious => self.find(self.scroll, true),
+            _ => {}
+        }
+    }
+
+    pub fn set_node(&mut self, node: &Node) {
+        let node = (node.kind() == NodeKind::File).then(|| node.clone());
+        if self.node.as_ref().map(Node::path) == node.as_ref().map(Node::path) {
+            return;
+        }
+
+        self.node = node;
+        self.lines.clear();
+        self.lines_load.clear();
+        self.scroll = 0;
+        self.search_input = None;
+
+        let Some(node) = self.node.clone() else {
+            return;
+        };
+
+        let package = self.package.clone();
+        self.lines_load.start(move |is_stale| {
+            let file = FileContext::new(&package, node);
+            if !TextHandler.detect(&file).unwrap_or(false) || is_stale() {
+                return None;
+            }
+
+            TextHandler.read(&file).ok().flatten().map(|text| {
+                text.lines()
+                    .map(|line| line.replace('\t', "    "))
+                    .collect()
+            })
+        });
+    }
+
+    fn receive(&mut self) {
+        if let Some(lines) = self.lines_load.poll() {
+            self.lines = lines.unwrap_or_default();
+        }
+    }
+
+    fn edit_search(&mut self, action: &Action) {
+        let Some(search_input) = self.search_input.as_mut() else {
+            return;
+        };
+
+        match action {
+            Action::InputChar(c) => search_input.push(*c),
+            Action::InputBackspace => {
+                search_input.pop();
+            }
+            Action::InputConfirm => {
+                self.search = search_input.to_ascii_lowercase();
+                self.search_input = None;
+                self.find(self.scroll, false);
+            }
+            Action::InputCancel => self.search_input = None,
+            _ => {}
+        }
+    }
+
+    /// Scrolls to the next line matching the search, starting from the given
+    /// line and wrapping around the text.
+    fn find(&mut self, from: usize, backward: bool) {
+        if self.search.is_empty() {
+            return;
+        }
+
+        let len = self.lines.len();
+        let matches = |&index: &usize| {
+            self.lines[index % len]
+                .to_ascii_lowercase()
+                .contains(&self.search)
+        };
+        let found = if backward {
+            (1..=len).map(|i| from + len - i).find(matches)
+        } else {
+            (0..len).map(|i| from + i).find(matches)
+        };
+
+        if let Some(index) = found {
+            self.scroll = index % len;
+        }
+    }
+
+    fn scroll_to(&mut self, scroll: usize) {
+        self.scroll = scroll.min(self.lines.len().saturating_sub(1));
+    }
+}
+
+impl WidgetRef for Preview {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let instructions = match &self.search_input {
+            Some(search_input) => Line::from(vec![
+                " Search: ".into(),
+                format!("{}_ ", search_input).yellow(),
+            ]),
+            None => Line::from(vec![
+                " Search ".into(),
+                "<?> ".light_blue(),
+                "Next ".into(),
+                "<n/N> ".light_blue(),
+                "Scroll ".into(),
+                "<J/K> ".light_blue(),
+                "Page ".into(),
+                "<[/]> ".light_blue(),
+            ]),
+        };
+        let instructions = Title::from(instructions)
+            .alignment(Alignment::Center)
+            .position(Position::Bottom);
+
+        let block = Block::default()
+            .title(format!(" Text ({} lines) ", self.lines.len()))
+            .title(instructions)
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1));
+
+        let height = block.inner(area).height as usize;
+        self.page.set(height);
+
+        let number_width = self.lines.len().to_string().len();
+        let number_style = Style::default().fg(Color::LightBlue);
+
+        let lines: Vec<Line> = self
+            .lines
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(height)
+            .map(|(index, line)| {
+                let mut spans = vec![Span::styled(
+                    format!("{:>width$} ", index + 1, width = number_width),
+                    number_style,
+                )];
+                spans.extend(highlight(line, &self.search));
+                Line::from(spans)
+            })
+            .collect();
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+/// Splits a line in spans, highlighting the ASCII case-insensitive matches of
+/// a lowercase search.
+fn highlight<'a>(line: &'a str, search: &str) -> Vec<Span<'a>> {
+    if search.is_empty() {
+        return vec![Span::raw(line)];
+    }
+
+    // ASCII lowercasing keeps the byte offsets of the line
+    let lowercase = line.to_ascii_lowercase();
+    let match_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for (index, found) in lowercase.match_indices(search) {
+        if index > start {
+            spans.push(Span::raw(&line[start..index]));
+        }
+        spans.push(Span::styled(&line[index..index + found.len()], match_style));
+        start = index + found.len();
+    }
+    if start < line.len() {
+        spans.push(Span::raw(&line[start..]));
+    }
+    spans
+}